@@ -0,0 +1,44 @@
+// Parsing throughput on synthetic receipts, run with `cargo bench`
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::fmt::Write;
+
+use konta::parser;
+
+const CATEGORIES: [&str; 5] = ["Frigorífico", "Mercearia", "Frutas e Legumes", "Congelados", "Casa"];
+
+// Receipts one after another, each with a few categories of items, a
+// weighed item, discounts, a delivery fee and a stated total
+fn receipts(count: usize) -> String {
+    let mut text = String::new();
+    for receipt in 0..count {
+        for category in CATEGORIES {
+            writeln!(text, "{}\n", category).unwrap();
+            for item in 0..5 {
+                let quantity = if item == 2 { "1.25 Kg".to_string() } else { (item % 3 + 1).to_string() };
+                let discount = if item == 1 { "-0,50 €" } else { "0,00 €" };
+                writeln!(
+                    text,
+                    "    Produto {} {} Marca Qualquer\n    {}\n    {}\n    {},{:02} € \n",
+                    receipt, item, quantity, discount, item + 1, (receipt * 7 + item) % 100,
+                ).unwrap();
+            }
+        }
+        writeln!(text, "Taxa de entrega: 3,99 €\nTotal: 123,45 €\n").unwrap();
+    }
+    text
+}
+
+fn parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    for count in [10, 100, 1000] {
+        let text = receipts(count);
+        group.throughput(Throughput::Bytes(text.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(count), &text, |b, text| {
+            b.iter(|| parser::parse(text.as_bytes(), "bench", "EUR").unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
@@ -0,0 +1,75 @@
+// Copies of unsaved sessions kept while the app runs, so a crash or a
+// closed terminal doesn't lose them. There's one per set of input files, in
+// $XDG_CACHE_HOME/konta or ~/.cache/konta, removed once the app exits
+// normally or the session is saved.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::model::Data;
+use crate::session;
+
+pub fn cache_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .map(|dir| dir.join("konta"))
+}
+
+// Where the copy of a session opened from `inputs` goes, named after the
+// first file and a hash of their full paths so receipts with the same
+// name, or the same receipt opened along with others, don't share it
+pub fn path(inputs: &[PathBuf]) -> Option<PathBuf> {
+    let mut full = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        full.push(fs::canonicalize(input).ok()?.to_string_lossy().into_owned());
+    }
+    let stem = inputs.first()?.file_stem().map_or_else(|| "session".into(), |stem| stem.to_string_lossy());
+    Some(cache_dir()?.join(format!("{}-{:016x}.json", stem, fnv1a(full.join("\n").as_bytes()))))
+}
+
+// Stable across builds, unlike std's hashers
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3))
+}
+
+// Writes a session JSON, replacing the previous copy at once so a crash
+// midway leaves the old one
+pub fn write(path: &Path, json: &str) -> Result<(), String> {
+    let error = |err: std::io::Error| format!("{}: {}", path.display(), err);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(error)?;
+    }
+    let partial = path.with_extension("json.partial");
+    fs::write(&partial, json).map_err(error)?;
+    fs::rename(&partial, path).map_err(error)
+}
+
+pub fn remove(path: &Path) {
+    // Nothing to do if it was never written
+    let _ = fs::remove_file(path);
+}
+
+// The copy left behind for `inputs`, unless one of them changed since or
+// the copy holds nothing more than `loaded`. Its receipts have to start
+// with those in `loaded`, any after them having been imported. Unreadable
+// copies are ignored.
+pub fn recover(path: &Path, inputs: &[PathBuf], loaded: &Data) -> Option<Data> {
+    let modified = |path: &Path| fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+    let copied = modified(path)?;
+    for input in inputs {
+        if copied < modified(input)? {
+            return None;
+        }
+    }
+    let text = fs::read_to_string(path).ok()?;
+    if text == session::to_json(loaded) {
+        return None;
+    }
+    let data = session::from_json(&text, &loaded.receipts[0].name, &loaded.receipts[0].currency).ok()?;
+    let same_receipts = data.receipts.len() >= loaded.receipts.len()
+        && data.receipts.iter().zip(&loaded.receipts).all(|(copy, receipt)| copy.name == receipt.name);
+    same_receipts.then_some(data)
+}
@@ -0,0 +1,69 @@
+use std::path::PathBuf;
+
+use konta::currency::parse_code;
+use konta::export::ExportFormat;
+
+pub const USAGE: &str = "\
+Usage: konta [options] <file>...
+
+Several receipts (or a session and receipts) are split together.
+
+Options:
+  --currency <code>  Currency of the receipts, e.g. USD
+  --settle <code>    Currency people's totals are computed in
+  --rates <file>     Exchange rates file
+  --export <format>  Print the split as markdown, text, html or json and exit
+  -o, --output <file>  Write the export to a file instead
+  -h, --help         Show this help";
+
+#[derive(Default)]
+pub struct Args {
+    pub files: Vec<PathBuf>,
+    pub currency: Option<String>,
+    pub settle: Option<String>,
+    pub rates: Option<PathBuf>,
+    pub export: Option<ExportFormat>,
+    pub output: Option<PathBuf>,
+    pub help: bool,
+}
+
+impl Args {
+    // Parses the arguments after the program name, accepting both
+    // "--option value" and "--option=value"
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, String> {
+        let mut parsed = Args::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let (option, inline_value) = match arg.split_once('=') {
+                Some((option, value)) if option.starts_with("--") => (option.to_string(), Some(value.to_string())),
+                _ => (arg.clone(), None),
+            };
+            let mut value = || {
+                inline_value.clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| format!("missing value for {}", option))
+            };
+            match option.as_str() {
+                "-h" | "--help" => parsed.help = true,
+                "--currency" => parsed.currency = Some(currency_code(&value()?)?),
+                "--settle" => parsed.settle = Some(currency_code(&value()?)?),
+                "--rates" => parsed.rates = Some(PathBuf::from(value()?)),
+                "--export" => {
+                    let name = value()?;
+                    parsed.export = Some(ExportFormat::from_name(&name)
+                        .ok_or_else(|| format!("unknown export format '{}'", name))?);
+                }
+                "--output" | "-o" => parsed.output = Some(PathBuf::from(value()?)),
+                _ if option.starts_with('-') && option.len() > 1 => {
+                    return Err(format!("unknown option {}", option));
+                }
+                _ => parsed.files.push(PathBuf::from(arg)),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+fn currency_code(text: &str) -> Result<String, String> {
+    parse_code(text).ok_or_else(|| format!("invalid currency '{}'", text))
+}
@@ -0,0 +1,239 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use tui::style::Color;
+
+use crate::currency::{currency_symbol, parse_code};
+use crate::export::ExportFormat;
+use crate::format::MoneyFormat;
+use crate::keymap::*;
+use crate::theme::*;
+
+pub struct Config {
+    pub keymap: Keymap,
+    pub theme: Theme,
+    pub format: MoneyFormat,
+    // Format written by the export key
+    pub export_format: ExportFormat,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            keymap: Keymap::default(),
+            theme: Theme::default(),
+            format: MoneyFormat::default(),
+            export_format: ExportFormat::Markdown,
+        }
+    }
+}
+
+// Layout of config.toml
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    keys: KeysConfig,
+    theme: ThemeConfig,
+    format: FormatConfig,
+    export: ExportConfig,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct ExportConfig {
+    // "markdown", "text" or "html"
+    format: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct FormatConfig {
+    locale: Option<String>,
+    // ISO code of the home currency
+    currency: Option<String>,
+    symbol: Option<String>,
+    decimal_separator: Option<char>,
+    // Empty for no grouping
+    thousands_separator: Option<String>,
+    // "before" or "after" the amount
+    symbol_position: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct ThemeConfig {
+    preset: Option<String>,
+    // "auto", "16", "256" or "truecolor"
+    colors: Option<String>,
+    selected_fg: Option<String>,
+    selected_bg: Option<String>,
+    pending_fg: Option<String>,
+    pending_bg: Option<String>,
+    focus: Option<String>,
+    muted: Option<String>,
+    ok: Option<String>,
+    error: Option<String>,
+    person_fg: Option<String>,
+    people: Option<Vec<String>>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct KeysConfig {
+    preset: Option<String>,
+    // Action name to one or more keys, replacing the preset's keys
+    #[serde(flatten)]
+    bindings: HashMap<String, OneOrMany>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+// $XDG_CONFIG_HOME/konta/config.toml, or ~/.config/konta/config.toml
+pub fn config_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|dir| dir.join("konta"))
+}
+
+impl Config {
+    // Missing config file means defaults, a broken one is an error
+    pub fn load() -> Result<Self, String> {
+        let path = match config_dir() {
+            Some(dir) => dir.join("config.toml"),
+            None => return Ok(Config::default()),
+        };
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(_) => return Ok(Config::default()),
+        };
+        Config::parse(&text).map_err(|err| format!("{}: {}", path.display(), err))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let file: ConfigFile = toml::from_str(text).map_err(|err| err.to_string())?;
+
+        let preset = file.keys.preset.as_deref().unwrap_or("default");
+        let mut keymap = Keymap::preset(preset)
+            .ok_or_else(|| format!("unknown key preset '{}'", preset))?;
+        for (name, keys) in file.keys.bindings {
+            let action = Action::from_name(&name)
+                .ok_or_else(|| format!("unknown action '{}'", name))?;
+            let keys = match keys {
+                OneOrMany::One(key) => vec![key],
+                OneOrMany::Many(keys) => keys,
+            };
+            keymap.unbind(action);
+            for key in keys {
+                let parsed = Key::parse(&key)
+                    .ok_or_else(|| format!("invalid key '{}' for '{}'", key, name))?;
+                keymap.bind(parsed, action);
+            }
+        }
+
+        let theme = theme_from_config(file.theme)?;
+        let format = format_from_config(file.format)?;
+        let export_format = match file.export.format {
+            Some(name) => ExportFormat::from_name(&name)
+                .ok_or_else(|| format!("unknown export format '{}'", name))?,
+            None => ExportFormat::Markdown,
+        };
+
+        Ok(Config { keymap, theme, format, export_format })
+    }
+}
+
+fn color_option(value: Option<String>) -> Result<Option<Color>, String> {
+    value
+        .map(|text| parse_color(&text).ok_or_else(|| format!("invalid colour '{}'", text)))
+        .transpose()
+}
+
+fn theme_from_config(config: ThemeConfig) -> Result<Theme, String> {
+    let mut theme = match config.preset.as_deref() {
+        Some(preset) => Theme::preset(preset)
+            .ok_or_else(|| format!("unknown theme preset '{}'", preset))?,
+        None => Theme::default(),
+    };
+    if let Some(colors) = config.colors {
+        theme.depth = ColorDepth::parse(&colors)
+            .ok_or_else(|| format!("unknown colour depth '{}'", colors))?;
+    }
+
+    if let Some(color) = color_option(config.selected_fg)? {
+        theme.selected = theme.selected.fg(color);
+    }
+    if let Some(color) = color_option(config.selected_bg)? {
+        theme.selected = theme.selected.bg(color);
+    }
+    if let Some(color) = color_option(config.pending_fg)? {
+        theme.pending = theme.pending.fg(color);
+    }
+    if let Some(color) = color_option(config.pending_bg)? {
+        theme.pending = theme.pending.bg(color);
+    }
+    if let Some(color) = color_option(config.focus)? {
+        theme.focus = theme.focus.fg(color);
+    }
+    if let Some(color) = color_option(config.muted)? {
+        theme.muted = theme.muted.fg(color);
+    }
+    if let Some(color) = color_option(config.ok)? {
+        theme.ok = theme.ok.fg(color);
+    }
+    if let Some(color) = color_option(config.error)? {
+        theme.error = theme.error.fg(color);
+    }
+    if let Some(color) = color_option(config.person_fg)? {
+        theme.person_fg = Some(color);
+    }
+    if let Some(people) = config.people {
+        theme.palette = people.into_iter()
+            .map(|text| color_option(Some(text)).map(Option::unwrap))
+            .collect::<Result<_, _>>()?;
+    }
+    Ok(theme)
+}
+
+fn format_from_config(config: FormatConfig) -> Result<MoneyFormat, String> {
+    let mut format = match config.locale.as_deref() {
+        Some(locale) => MoneyFormat::preset(locale)
+            .ok_or_else(|| format!("unknown locale '{}'", locale))?,
+        None => MoneyFormat::default(),
+    };
+    if let Some(currency) = config.currency {
+        format.currency = parse_code(&currency)
+            .ok_or_else(|| format!("invalid currency '{}'", currency))?;
+        format.symbol = currency_symbol(&format.currency).to_string();
+    }
+    if let Some(symbol) = config.symbol {
+        format.symbol = symbol;
+    }
+    if let Some(separator) = config.decimal_separator {
+        format.decimal_separator = separator;
+    }
+    if let Some(separator) = config.thousands_separator {
+        let mut chars = separator.chars();
+        format.thousands_separator = match (chars.next(), chars.next()) {
+            (None, _) => None,
+            (Some(c), None) => Some(c),
+            _ => return Err(format!("invalid thousands separator '{}'", separator)),
+        };
+    }
+    if let Some(position) = config.symbol_position {
+        format.symbol_after = match position.as_str() {
+            "before" => false,
+            "after" => true,
+            _ => return Err(format!("invalid symbol position '{}'", position)),
+        };
+    }
+    Ok(format)
+}
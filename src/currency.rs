@@ -0,0 +1,146 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+// Exchange rates as units of each currency per unit of `base`
+pub struct ExchangeRates {
+    base: String,
+    rates: HashMap<String, f32>,
+}
+
+// Layout of rates.toml, also used in saved sessions
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RatesTable {
+    pub base: String,
+    pub rates: BTreeMap<String, f32>,
+}
+
+impl ExchangeRates {
+    pub fn new(base: &str) -> Self {
+        ExchangeRates {
+            base: base.to_string(),
+            rates: HashMap::new(),
+        }
+    }
+
+    // Reads a rates file, e.g.
+    //   base = "EUR"
+    //   [rates]
+    //   USD = 1.08
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|err| format!("{}: {}", path.display(), err))?;
+        ExchangeRates::parse(&text).map_err(|err| format!("{}: {}", path.display(), err))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let table: RatesTable = toml::from_str(text).map_err(|err| err.to_string())?;
+        ExchangeRates::from_table(table)
+    }
+
+    pub fn from_table(table: RatesTable) -> Result<Self, String> {
+        let base = parse_code(&table.base)
+            .ok_or_else(|| format!("invalid currency '{}'", table.base))?;
+        let mut rates = ExchangeRates::new(&base);
+        for (code, rate) in table.rates {
+            let code = parse_code(&code)
+                .ok_or_else(|| format!("invalid currency '{}'", code))?;
+            if rate <= 0.0 {
+                return Err(format!("invalid rate {} for {}", rate, code));
+            }
+            rates.rates.insert(code, rate);
+        }
+        Ok(rates)
+    }
+
+    pub fn to_table(&self) -> RatesTable {
+        RatesTable {
+            base: self.base.clone(),
+            rates: self.rates.iter().map(|(code, rate)| (code.clone(), *rate)).collect(),
+        }
+    }
+
+    // Units of `code` per unit of the base currency
+    fn rate(&self, code: &str) -> Option<f32> {
+        if code == self.base {
+            Some(1.0)
+        }
+        else {
+            self.rates.get(code).copied()
+        }
+    }
+
+    pub fn convert(&self, amount: f32, from: &str, to: &str) -> Option<f32> {
+        if from == to {
+            return Some(amount);
+        }
+        Some(amount / self.rate(from)? * self.rate(to)?)
+    }
+
+    // Sets how many units of `to` one unit of `from` is worth. At least
+    // one of them must already be known, returns false otherwise.
+    pub fn set(&mut self, from: &str, to: &str, rate: f32) -> bool {
+        if let Some(to_rate) = self.rate(to) {
+            if from != self.base {
+                self.rates.insert(from.to_string(), to_rate / rate);
+                return true;
+            }
+        }
+        if let Some(from_rate) = self.rate(from) {
+            if to != self.base {
+                self.rates.insert(to.to_string(), from_rate * rate);
+                return true;
+            }
+        }
+        false
+    }
+
+    // Takes in the rates of another table for currencies not known here,
+    // as long as it relates them to one that is
+    pub fn merge(&mut self, other: &ExchangeRates) {
+        let codes: Vec<&String> = std::iter::once(&other.base).chain(other.rates.keys()).collect();
+        let anchor = match codes.iter().find(|code| self.rate(code).is_some()) {
+            Some(anchor) => anchor.to_string(),
+            None => return,
+        };
+        for code in codes {
+            if self.rate(code).is_none() {
+                // Every currency in a table converts to every other
+                let rate = other.convert(1.0, code, &anchor).unwrap();
+                self.set(code, &anchor, rate);
+            }
+        }
+    }
+}
+
+// Uppercase ISO 4217 code, e.g. "usd" -> "USD"
+pub fn parse_code(text: &str) -> Option<String> {
+    let code = text.trim().to_uppercase();
+    if code.len() == 3 && code.chars().all(|c| c.is_ascii_alphabetic()) {
+        Some(code)
+    }
+    else {
+        None
+    }
+}
+
+// Symbol usually written next to amounts, the code itself if unknown
+pub fn currency_symbol(code: &str) -> &str {
+    match code {
+        "EUR" => "€",
+        "USD" => "$",
+        "GBP" => "£",
+        "BRL" => "R$",
+        "JPY" | "CNY" => "¥",
+        "INR" => "₹",
+        "KRW" => "₩",
+        "PLN" => "zł",
+        "CZK" => "Kč",
+        "TRY" => "₺",
+        "ILS" => "₪",
+        _ => code,
+    }
+}
@@ -0,0 +1,279 @@
+use std::fmt::Write;
+use std::path::PathBuf;
+
+use crate::format::MoneyFormat;
+use crate::session;
+use crate::model::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Markdown,
+    Text,
+    Html,
+    // The whole session, see `session`
+    Json,
+}
+
+impl ExportFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "markdown" | "md" => Some(ExportFormat::Markdown),
+            "text" | "txt" => Some(ExportFormat::Text),
+            "html" | "htm" => Some(ExportFormat::Html),
+            "json" => Some(ExportFormat::Json),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "md",
+            ExportFormat::Text => "txt",
+            ExportFormat::Html => "html",
+            ExportFormat::Json => "json",
+        }
+    }
+}
+
+// "<receipt>-split.md" in the working directory
+pub fn default_path(data: &Data, format: ExportFormat) -> PathBuf {
+    let name = data.receipts.first().map_or("receipt", |receipt| receipt.name.as_str());
+    PathBuf::from(format!("{}-split.{}", name, format.extension()))
+}
+
+// Totals can't be worked out without a rate for every receipt's
+// currency. Sessions are saved as they are.
+pub fn check_rates(data: &Data, format: ExportFormat) -> Result<(), String> {
+    let missing = data.missing_rates();
+    if missing.is_empty() || format == ExportFormat::Json {
+        return Ok(());
+    }
+    Err(format!("no rate from {} to {}", missing.join(", "), data.settlement_currency))
+}
+
+// What gets exported, already formatted
+struct Summary {
+    title: String,
+    people: Vec<PersonSummary>,
+    total: String,
+    // Price of items nobody owns, if any
+    unassigned: Option<String>,
+    // "Ana owes jojo 1,50 €"
+    transfers: Vec<String>,
+    // Whether any receipt has a payer to settle with
+    has_payer: bool,
+}
+
+struct PersonSummary {
+    name: String,
+    // (description, share, amount)
+    lines: Vec<(String, String, String)>,
+    total: String,
+}
+
+fn summarize(data: &Data, money: &MoneyFormat) -> Summary {
+    let currency = data.settlement_currency.as_str();
+    let share = |fraction: f32| {
+        if fraction >= 0.9995 { String::new() } else { format!("{:.0}%", fraction * 100.0) }
+    };
+
+    let mut people = Vec::with_capacity(data.people.len());
+    let mut total = 0.0;
+    for (person_idx, name) in data.people.iter().enumerate() {
+        let breakdown = data.person_breakdown(person_idx);
+        let mut lines = Vec::with_capacity(breakdown.lines());
+        for category in &breakdown.categories {
+            for (item_idx, fraction, amount) in &category.items {
+                lines.push((
+                    data.items[*item_idx].description.clone(),
+                    share(*fraction),
+                    money.money_in(*amount, currency),
+                ));
+            }
+        }
+        for (charge_idx, fraction, amount) in &breakdown.charges {
+            lines.push((
+                data.charges[*charge_idx].description.clone(),
+                share(*fraction),
+                money.money_in(*amount, currency),
+            ));
+        }
+        total += breakdown.total;
+        people.push(PersonSummary {
+            name: name.clone(),
+            lines,
+            total: money.money_in(breakdown.total, currency),
+        });
+    }
+
+    let unassigned = data.unassigned_total();
+    Summary {
+        title: data.receipts.iter()
+            .map(|receipt| receipt.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", "),
+        people,
+        total: money.money_in(total, currency),
+        unassigned: (unassigned >= 0.005).then(|| money.money_in(unassigned, currency)),
+        transfers: data.settlements().into_iter()
+            .map(|(from, to, amount)| format!(
+                "{} owes {} {}",
+                data.people[from],
+                data.people[to],
+                money.money_in(amount, currency),
+            ))
+            .collect(),
+        has_payer: data.receipts.iter().any(|receipt| receipt.payer.is_some()),
+    }
+}
+
+pub fn render(data: &Data, money: &MoneyFormat, format: ExportFormat) -> String {
+    match format {
+        ExportFormat::Markdown => render_markdown(&summarize(data, money)),
+        ExportFormat::Text => render_text(&summarize(data, money)),
+        ExportFormat::Html => render_html(&summarize(data, money)),
+        ExportFormat::Json => session::to_json(data),
+    }
+}
+
+// Writing to a String never fails, hence the unwraps below
+
+fn render_markdown(summary: &Summary) -> String {
+    // Table cells can't contain pipes
+    let cell = |text: &str| text.replace('|', "\\|");
+    let mut out = String::new();
+    writeln!(out, "# Split: {}", summary.title).unwrap();
+    for person in &summary.people {
+        writeln!(out, "\n## {}: {}\n", person.name, person.total).unwrap();
+        if person.lines.is_empty() {
+            writeln!(out, "Nothing assigned.").unwrap();
+            continue;
+        }
+        writeln!(out, "| Item | Share | Amount |").unwrap();
+        writeln!(out, "|:-----|------:|-------:|").unwrap();
+        for (description, share, amount) in &person.lines {
+            writeln!(out, "| {} | {} | {} |", cell(description), share, amount).unwrap();
+        }
+        writeln!(out, "| **Total** | | **{}** |", person.total).unwrap();
+    }
+    writeln!(out, "\n**Total: {}**", summary.total).unwrap();
+    if let Some(unassigned) = &summary.unassigned {
+        writeln!(out, "\nNot assigned to anyone: {}", unassigned).unwrap();
+    }
+    if summary.has_payer {
+        writeln!(out, "\n## Who owes whom\n").unwrap();
+        if summary.transfers.is_empty() {
+            writeln!(out, "Nobody owes anything.").unwrap();
+        }
+        for transfer in &summary.transfers {
+            writeln!(out, "- {}", transfer).unwrap();
+        }
+    }
+    out
+}
+
+fn render_text(summary: &Summary) -> String {
+    let width = |column: fn(&(String, String, String)) -> &String| {
+        summary.people.iter()
+            .flat_map(|person| &person.lines)
+            .map(|line| column(line).chars().count())
+            .max()
+            .unwrap_or(0)
+    };
+    let description_width = width(|line| &line.0);
+    let share_width = width(|line| &line.1);
+    let amount_width = width(|line| &line.2);
+
+    let mut out = String::new();
+    writeln!(out, "Split: {}", summary.title).unwrap();
+    for person in &summary.people {
+        writeln!(out, "\n{}: {}", person.name, person.total).unwrap();
+        for (description, share, amount) in &person.lines {
+            writeln!(
+                out,
+                "  {:<dw$}  {:>sw$}  {:>aw$}",
+                description, share, amount,
+                dw = description_width, sw = share_width, aw = amount_width,
+            ).unwrap();
+        }
+    }
+    writeln!(out, "\nTotal: {}", summary.total).unwrap();
+    if let Some(unassigned) = &summary.unassigned {
+        writeln!(out, "Not assigned to anyone: {}", unassigned).unwrap();
+    }
+    if summary.has_payer {
+        writeln!(out).unwrap();
+        if summary.transfers.is_empty() {
+            writeln!(out, "Nobody owes anything.").unwrap();
+        }
+        for transfer in &summary.transfers {
+            writeln!(out, "{}", transfer).unwrap();
+        }
+    }
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn render_html(summary: &Summary) -> String {
+    let title = escape_html(&summary.title);
+    let mut out = String::new();
+    writeln!(out, "<!DOCTYPE html>").unwrap();
+    writeln!(out, "<html>\n<head>\n<meta charset=\"utf-8\">").unwrap();
+    writeln!(out, "<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">").unwrap();
+    writeln!(out, "<title>Split: {}</title>", title).unwrap();
+    writeln!(out, "<style>").unwrap();
+    writeln!(out, "body {{ font-family: sans-serif; max-width: 40em; margin: 2em auto; padding: 0 1em; }}").unwrap();
+    writeln!(out, "table {{ border-collapse: collapse; width: 100%; }}").unwrap();
+    writeln!(out, "td, th {{ padding: 0.2em 0.5em; border-bottom: 1px solid #ddd; }}").unwrap();
+    writeln!(out, "td.number, th.number {{ text-align: right; white-space: nowrap; }}").unwrap();
+    writeln!(out, "tr.total td {{ font-weight: bold; border-bottom: none; }}").unwrap();
+    writeln!(out, "</style>\n</head>\n<body>").unwrap();
+    writeln!(out, "<h1>Split: {}</h1>", title).unwrap();
+    for person in &summary.people {
+        writeln!(out, "<h2>{}: {}</h2>", escape_html(&person.name), escape_html(&person.total)).unwrap();
+        if person.lines.is_empty() {
+            writeln!(out, "<p>Nothing assigned.</p>").unwrap();
+            continue;
+        }
+        writeln!(out, "<table>").unwrap();
+        writeln!(out, "<tr><th>Item</th><th class=\"number\">Share</th><th class=\"number\">Amount</th></tr>").unwrap();
+        for (description, share, amount) in &person.lines {
+            writeln!(
+                out,
+                "<tr><td>{}</td><td class=\"number\">{}</td><td class=\"number\">{}</td></tr>",
+                escape_html(description), share, escape_html(amount),
+            ).unwrap();
+        }
+        writeln!(
+            out,
+            "<tr class=\"total\"><td>Total</td><td></td><td class=\"number\">{}</td></tr>",
+            escape_html(&person.total),
+        ).unwrap();
+        writeln!(out, "</table>").unwrap();
+    }
+    writeln!(out, "<p><strong>Total: {}</strong></p>", escape_html(&summary.total)).unwrap();
+    if let Some(unassigned) = &summary.unassigned {
+        writeln!(out, "<p>Not assigned to anyone: {}</p>", escape_html(unassigned)).unwrap();
+    }
+    if summary.has_payer {
+        writeln!(out, "<h2>Who owes whom</h2>").unwrap();
+        if summary.transfers.is_empty() {
+            writeln!(out, "<p>Nobody owes anything.</p>").unwrap();
+        }
+        else {
+            writeln!(out, "<ul>").unwrap();
+            for transfer in &summary.transfers {
+                writeln!(out, "<li>{}</li>", escape_html(transfer)).unwrap();
+            }
+            writeln!(out, "</ul>").unwrap();
+        }
+    }
+    writeln!(out, "</body>\n</html>").unwrap();
+    out
+}
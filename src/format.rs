@@ -0,0 +1,119 @@
+use crate::currency::currency_symbol;
+
+// How amounts of money are written, defaulting to Portuguese receipts'
+// "1234,56 €"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MoneyFormat {
+    // Home currency, used when a receipt doesn't say otherwise
+    pub currency: String,
+    // Symbol written for the home currency
+    pub symbol: String,
+    pub decimal_separator: char,
+    // Grouping of thousands, none by default
+    pub thousands_separator: Option<char>,
+    pub symbol_after: bool,
+}
+
+impl Default for MoneyFormat {
+    fn default() -> Self {
+        MoneyFormat::preset("pt-PT").unwrap()
+    }
+}
+
+impl MoneyFormat {
+    pub fn preset(locale: &str) -> Option<Self> {
+        let (currency, decimal_separator, thousands_separator, symbol_after) = match locale {
+            "pt-PT" | "pt" => ("EUR", ',', None, true),
+            "pt-BR" => ("BRL", ',', Some('.'), false),
+            "es-ES" | "es" | "fr-FR" | "fr" | "it-IT" | "it" => ("EUR", ',', None, true),
+            "de-DE" | "de" => ("EUR", ',', Some('.'), true),
+            "en-GB" => ("GBP", '.', Some(','), false),
+            "en-US" | "en" => ("USD", '.', Some(','), false),
+            _ => return None,
+        };
+        Some(MoneyFormat {
+            currency: currency.to_string(),
+            symbol: currency_symbol(currency).to_string(),
+            decimal_separator,
+            thousands_separator,
+            symbol_after,
+        })
+    }
+
+    // "1.234,56", rounded to cents, without the currency symbol
+    pub fn number(&self, amount: f32) -> String {
+        self.digits(amount, self.thousands_separator)
+    }
+
+    // "1234,56", ready to be edited in a prompt
+    pub fn editable(&self, amount: f32) -> String {
+        self.digits(amount, None)
+    }
+
+    fn digits(&self, amount: f32, thousands_separator: Option<char>) -> String {
+        let cents = (amount.abs() * 100.0).round() as u64;
+        let units = (cents / 100).to_string();
+        let mut text = String::with_capacity(units.len() + 4);
+        if amount < 0.0 && cents != 0 {
+            text.push('-');
+        }
+        for (i, digit) in units.chars().enumerate() {
+            let remaining = units.len() - i;
+            if i > 0 && remaining.is_multiple_of(3) {
+                if let Some(separator) = thousands_separator {
+                    text.push(separator);
+                }
+            }
+            text.push(digit);
+        }
+        text.push(self.decimal_separator);
+        text.push_str(&format!("{:02}", cents % 100));
+        text
+    }
+
+    // "2" or "1,83 Kg", with up to three decimals
+    pub fn quantity(&self, quantity: f32, unit: &str) -> String {
+        let number = format!("{:.3}", quantity);
+        let number = number.trim_end_matches('0').trim_end_matches('.');
+        let number = number.replace('.', &self.decimal_separator.to_string());
+        if unit.is_empty() { number } else { format!("{} {}", number, unit) }
+    }
+
+    // "0,9259" or "0,0061", an exchange rate with up to four decimals
+    pub fn rate(&self, rate: f32) -> String {
+        let number = format!("{:.4}", rate);
+        let number = number.trim_end_matches('0').trim_end_matches('.');
+        number.replace('.', &self.decimal_separator.to_string())
+    }
+
+    // "1234,56 €" or "$1,234.56", in the home currency
+    pub fn money(&self, amount: f32) -> String {
+        self.money_in(amount, &self.currency)
+    }
+
+    // Amount in any currency, given its ISO code
+    pub fn money_in(&self, amount: f32, currency: &str) -> String {
+        let symbol = if currency == self.currency { self.symbol.as_str() } else { currency_symbol(currency) };
+        let number = self.number(amount);
+        if symbol.is_empty() {
+            number
+        }
+        else if self.symbol_after {
+            format!("{} {}", number, symbol)
+        }
+        else {
+            // Codes like "CHF" need a space, symbols don't
+            let space = if symbol.chars().all(|c| c.is_ascii_alphabetic()) { " " } else { "" };
+            match number.strip_prefix('-') {
+                Some(number) => format!("-{}{}{}", symbol, space, number),
+                None => format!("{}{}{}", symbol, space, number),
+            }
+        }
+    }
+
+    // Like `money_in`, but always with a sign, for differences
+    pub fn signed_money_in(&self, amount: f32, currency: &str) -> String {
+        let text = self.money_in(amount, currency);
+        if text.starts_with('-') { text } else { format!("+{}", text) }
+    }
+}
@@ -1,192 +1,958 @@
-use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::fs;
+use std::path::PathBuf;
 
+use crate::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use crate::export;
+use crate::keymap::Action;
+use crate::model::*;
+use crate::parser::parse_price;
 use crate::state::*;
+use crate::text_input::TextInput;
 
-pub enum Event {
-    Input(KeyEvent),
-    Tick,
+// Actions each window responds to, most relevant first, used for the
+// help overlay and footer hints
+pub fn window_actions(focused: &FocusedWindow) -> &'static [(Action, &'static str)] {
+    match focused {
+        FocusedWindow::Items(_) => &[
+            (Action::Select, "Set item's owner"),
+            (Action::Down, "Next item"),
+            (Action::Up, "Previous item"),
+            (Action::AddPerson, "Add person"),
+            (Action::AssignRest, "Set owner of all unowned items"),
+            (Action::EditItem, "Edit item"),
+            (Action::AddItem, "Add item"),
+            (Action::DuplicateItem, "Duplicate item"),
+            (Action::SplitItem, "Split off part of the quantity"),
+            (Action::Delete, "Remove item"),
+            (Action::Import, "Import another receipt"),
+            (Action::SetTotal, "Enter the receipt's stated total"),
+            (Action::AddCharge, "Add charge"),
+            (Action::AddCredit, "Add voucher or card credit"),
+            (Action::SetRate, "Set the receipt's exchange rate"),
+            (Action::Export, "Export the split"),
+            (Action::Save, "Save the session"),
+            (Action::ScrollLeft, "Scroll descriptions left"),
+            (Action::ScrollRight, "Scroll descriptions right"),
+            (Action::ToggleWrap, "Wrap long descriptions"),
+            (Action::ToggleSidebar, "Show or hide the side panels"),
+            (Action::NextPanel, "Focus next panel"),
+            (Action::PrevPanel, "Focus previous panel"),
+            (Action::Quit, "Quit"),
+        ],
+        FocusedWindow::People(_) => &[
+            (Action::Select, "Show person's breakdown"),
+            (Action::Down, "Next person"),
+            (Action::Up, "Previous person"),
+            (Action::AddPerson, "Add person"),
+            (Action::Delete, "Remove person"),
+            (Action::SetPayer, "Mark as the one who paid the selected receipt"),
+            (Action::Export, "Export the split"),
+            (Action::Save, "Save the session"),
+            (Action::NextPanel, "Focus next panel"),
+            (Action::PrevPanel, "Focus previous panel"),
+            (Action::Quit, "Quit"),
+        ],
+        FocusedWindow::Charges(_) => &[
+            (Action::Select, "Change how the charge is split"),
+            (Action::Down, "Next charge"),
+            (Action::Up, "Previous charge"),
+            (Action::AddCharge, "Add charge"),
+            (Action::AddCredit, "Add voucher or card credit"),
+            (Action::Delete, "Remove charge"),
+            (Action::Save, "Save the session"),
+            (Action::NextPanel, "Focus next panel"),
+            (Action::PrevPanel, "Focus previous panel"),
+            (Action::Quit, "Quit"),
+        ],
+        FocusedWindow::OwnerSelector(_,_,_) => &[
+            (Action::Select, "Set owner"),
+            (Action::PickPerson, "Add as co-owner and keep choosing"),
+            (Action::Down, "Next person"),
+            (Action::Up, "Previous person"),
+            (Action::Cancel, "Back to items"),
+            (Action::Quit, "Quit"),
+        ],
+        FocusedWindow::RestOwnerSelector(_) => &[
+            (Action::Select, "Set owner of all unowned items"),
+            (Action::Down, "Next person"),
+            (Action::Up, "Previous person"),
+            (Action::Cancel, "Back to items"),
+            (Action::Quit, "Quit"),
+        ],
+        FocusedWindow::ChargeSplitSelector(_,_,_) => &[
+            (Action::SplitEqual, "Split equally"),
+            (Action::SplitProportional, "Split by each person's subtotal"),
+            (Action::Select, "Split between the chosen people"),
+            (Action::PickPerson, "Choose person and keep choosing"),
+            (Action::Down, "Next person"),
+            (Action::Up, "Previous person"),
+            (Action::Cancel, "Keep current split"),
+            (Action::Quit, "Quit"),
+        ],
+        FocusedWindow::PersonDetail(_,_) => &[
+            (Action::Down, "Scroll down"),
+            (Action::Up, "Scroll up"),
+            (Action::Cancel, "Back to people"),
+            (Action::Quit, "Quit"),
+        ],
+        FocusedWindow::AddPerson(_)
+        | FocusedWindow::SetTotal(_,_)
+        | FocusedWindow::AddCharge(_,_)
+        | FocusedWindow::AddCredit(_,_)
+        | FocusedWindow::AddItem(_,_)
+        | FocusedWindow::SplitItem(_,_)
+        | FocusedWindow::ImportReceipt(_)
+        | FocusedWindow::SetRate(_,_) => &[
+            (Action::Select, "Confirm"),
+            (Action::Cancel, "Cancel"),
+        ],
+        FocusedWindow::EditItem(_,_,_) => &[
+            (Action::Select, "Save and finish"),
+            (Action::NextPanel, "Save and edit next field"),
+            (Action::PrevPanel, "Save and edit previous field"),
+            (Action::Cancel, "Discard this field"),
+        ],
+    }
+}
+
+// Keys of an open dialog, the confirming one being also `y` and the
+// cancelling one `n`
+pub const DIALOG_ACTIONS: [(Action, &str); 2] = [
+    (Action::Select, "Confirm"),
+    (Action::Cancel, "Cancel"),
+];
+
+// Routes an event to the focused window's handler, returns true to exit
+pub fn handle_input(event: &Event, app: &mut AppState) -> bool {
+    // Dialogs take every key until answered
+    if app.dialog.is_some() {
+        return dialog_input_handler(event, app);
+    }
+    // Pasting outside a prompt is the same as typing
+    if let Event::Paste(text) = event {
+        if !app.focused.is_text_input() {
+            for c in text.chars() {
+                if handle_input(&Event::Input(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)), app) {
+                    return true;
+                }
+            }
+            return false;
+        }
+    }
+    if let Event::Input(key) = event {
+        app.status = None;
+        // Any key closes the help overlay
+        if app.show_help {
+            app.show_help = false;
+            return false;
+        }
+        if !app.focused.is_text_input() && app.config.keymap.action(key) == Some(Action::Help) {
+            app.show_help = true;
+            return false;
+        }
+    }
+    // Kept for when the focus moves to the people panel
+    app.last_receipt = app.current_receipt();
+    match app.focused {
+        FocusedWindow::Items(idx) => app.last_panel = FocusedWindow::Items(idx),
+        FocusedWindow::People(idx) => app.last_panel = FocusedWindow::People(idx),
+        FocusedWindow::Charges(idx) => app.last_panel = FocusedWindow::Charges(idx),
+        _ => {}
+    }
+
+    match app.focused {
+        FocusedWindow::Items(_) => items_input_handler(event, app),
+        FocusedWindow::People(_) => people_input_handler(event, app),
+        FocusedWindow::Charges(_) => charges_input_handler(event, app),
+        FocusedWindow::OwnerSelector(_,_,_) => owner_selector_input_handler(event, app),
+        FocusedWindow::RestOwnerSelector(_) => rest_owner_selector_input_handler(event, app),
+        FocusedWindow::AddPerson(_) => add_person_input_handler(event, app),
+        FocusedWindow::SetTotal(_,_) => set_total_input_handler(event, app),
+        FocusedWindow::SetRate(_,_) => set_rate_input_handler(event, app),
+        FocusedWindow::EditItem(_,_,_) => edit_item_input_handler(event, app),
+        FocusedWindow::AddItem(_,_) => add_item_input_handler(event, app),
+        FocusedWindow::SplitItem(_,_) => split_item_input_handler(event, app),
+        FocusedWindow::ImportReceipt(_) => import_receipt_input_handler(event, app),
+        FocusedWindow::AddCharge(_,_) | FocusedWindow::AddCredit(_,_) => add_charge_input_handler(event, app),
+        FocusedWindow::ChargeSplitSelector(_,_,_) => charge_split_selector_input_handler(event, app),
+        FocusedWindow::PersonDetail(_,_) => person_detail_input_handler(event, app),
+    }
+}
+
+// Writes the split in the configured format to the working directory
+fn export_split(app: &mut AppState) {
+    let format = app.config.export_format;
+    if let Err(err) = export::check_rates(&app.data, format) {
+        app.status = Some(format!("Export failed: {}", err));
+        return;
+    }
+    let path = export::default_path(&app.data, format);
+    let text = export::render(&app.data, &app.config.format, format);
+    app.status = Some(match fs::write(&path, text) {
+        Ok(()) => format!("Exported to {}", path.display()),
+        Err(err) => format!("Export failed: {}", err),
+    });
+}
+
+fn save_session(app: &mut AppState) {
+    if let Some(path) = app.session_path.as_deref().filter(|path| !app.session_owned && path.exists()) {
+        app.dialog = Some(Dialog {
+            title: "Overwrite",
+            message: format!("{} already exists. Save over it?", path.display()),
+            confirm: "Overwrite",
+            action: DialogAction::Overwrite,
+        });
+        return;
+    }
+    write_session(app);
+}
+
+fn write_session(app: &mut AppState) {
+    app.status = Some(match app.save() {
+        Ok(path) => format!("Saved to {}", path.display()),
+        Err(err) => format!("Save failed: {}", err),
+    });
+}
+
+// Quits right away unless there are unsaved changes to ask about
+fn quit(app: &mut AppState) -> bool {
+    if !app.is_dirty() {
+        return true;
+    }
+    app.dialog = Some(Dialog {
+        title: "Quit",
+        message: "Quit without saving? The changes since the session was last saved will be lost.".into(),
+        confirm: "Quit",
+        action: DialogAction::Quit,
+    });
+    false
+}
+
+pub fn dialog_input_handler(event: &Event, app: &mut AppState) -> bool {
+    let key = match event {
+        Event::Input(key) => key,
+        Event::Paste(_) | Event::Resize(_,_) => return false,
+    };
+    let confirmed = match (app.config.keymap.action(key), key.code) {
+        (Some(Action::Select), _) | (_, KeyCode::Char('y' | 'Y')) => true,
+        (Some(Action::Cancel), _) | (_, KeyCode::Char('n' | 'N')) => false,
+        _ => return false,
+    };
+    let dialog = app.dialog.take();
+    match dialog.map(|dialog| dialog.action) {
+        Some(DialogAction::Quit) if confirmed => return true,
+        Some(DialogAction::AssignRest(person_idx)) if confirmed => {
+            app.data.set_rest_items_owner(person_idx);
+            app.return_to_panel();
+        }
+        Some(DialogAction::RemovePerson(person_idx)) if confirmed => {
+            app.data.remove_person(person_idx);
+            if let FocusedWindow::People(idx) = &mut app.focused {
+                *idx = idx.saturating_sub(1);
+            }
+        }
+        Some(DialogAction::RemoveItem(item_idx)) if confirmed => {
+            app.data.remove_item(item_idx);
+            if let FocusedWindow::Items(idx) = &mut app.focused {
+                *idx = (*idx).min(app.data.items.len().saturating_sub(1));
+            }
+        }
+        Some(DialogAction::RemoveCharge(charge_idx)) if confirmed => {
+            app.data.remove_charge(charge_idx);
+            if let FocusedWindow::Charges(idx) = &mut app.focused {
+                *idx = (*idx).min(app.data.charges.len().saturating_sub(1));
+            }
+        }
+        Some(DialogAction::Overwrite) if confirmed => write_session(app),
+        Some(DialogAction::Recover(data)) if confirmed => {
+            app.data = *data;
+            // Receipts imported before the crash have no file to watch
+            app.receipt_files.resize(app.data.receipts.len(), None);
+        }
+        Some(DialogAction::Recover(_)) => app.discard_autosave(),
+        _ => {}
+    }
+    false
+}
+
+// Characters scrolled per key press when descriptions don't fit
+const SCROLL_STEP: usize = 5;
+
+// Moves focus to the next (or previous) panel: Items, People, Charges
+fn cycle_focus(app: &mut AppState, forward: bool) {
+    let panel = match app.focused {
+        FocusedWindow::Items(_) => 0,
+        FocusedWindow::People(_) => 1,
+        FocusedWindow::Charges(_) => 2,
+        _ => return,
+    };
+    let next = if forward { (panel + 1) % 3 } else { (panel + 2) % 3 };
+    app.focused = match next {
+        0 => FocusedWindow::Items(0),
+        1 => FocusedWindow::People(0),
+        _ => FocusedWindow::Charges(0),
+    };
 }
 
 pub fn items_input_handler(input: &Event, app: &mut AppState) -> bool {
     if let FocusedWindow::Items(idx) = &mut app.focused {
         match input {
-            Event::Input(event) => match event.code {
-                KeyCode::Char('q') | KeyCode::Char('Q') => {
-                    return true;
+            Event::Input(event) => match app.config.keymap.action(event) {
+                Some(Action::Quit) => return quit(app),
+                Some(Action::Down)
+                    if !app.data.items.is_empty() && *idx < app.data.items.len() - 1 =>
+                {
+                    *idx += 1;
                 }
-                KeyCode::Down | KeyCode::Char('j') => {
-                    if app.data.items.len() > 0 && *idx < app.data.items.len() - 1 {
-                        *idx += 1;
-                    }
-                }
-                KeyCode::Up | KeyCode::Char('k') => {
-                    if *idx > 0usize {
-                        *idx -= 1;
-                    }
+                Some(Action::Up) if *idx > 0usize => {
+                    *idx -= 1;
                 }
                 // Select Owner of this item purchase
-                KeyCode::Enter => {
-                    if app.data.items.len() > 0 {
-                        app.focused = FocusedWindow::OwnerSelector(*idx,0,vec![]);
-                    }
+                Some(Action::Select) if !app.data.items.is_empty() => {
+                    app.focused = FocusedWindow::OwnerSelector(*idx,0,vec![]);
+                }
+                Some(Action::EditItem) if !app.data.items.is_empty() => {
+                    let item_idx = *idx;
+                    let input = app.item_field_input(item_idx, ItemField::Description);
+                    app.focused = FocusedWindow::EditItem(item_idx, ItemField::Description, input);
+                }
+                // Items missing from the receipt, e.g. bought in cash
+                Some(Action::AddItem) => {
+                    app.focused = FocusedWindow::AddItem(app.current_receipt(), TextInput::new());
+                }
+                Some(Action::DuplicateItem) if !app.data.items.is_empty() => {
+                    *idx = app.data.duplicate_item(*idx);
+                }
+                Some(Action::SplitItem) if !app.data.items.is_empty() => {
+                    app.focused = FocusedWindow::SplitItem(*idx, TextInput::numeric());
+                }
+                // More receipts split in the same session
+                Some(Action::Import) => {
+                    app.focused = FocusedWindow::ImportReceipt(TextInput::new());
+                }
+                Some(Action::Delete) if *idx < app.data.items.len() => {
+                    app.dialog = Some(Dialog {
+                        title: "Remove Item",
+                        message: format!("Remove {}?", app.data.items[*idx].description),
+                        confirm: "Remove",
+                        action: DialogAction::RemoveItem(*idx),
+                    });
                 }
                 // Set Owner for all unowned items
-                KeyCode::Char('r') | KeyCode::Char('R') => {
-                    if app.data.items.len() > 0 {
-                        app.focused = FocusedWindow::RestOwnerSelector(0);
-                    }
+                Some(Action::AssignRest) if !app.data.items.is_empty() => {
+                    app.focused = FocusedWindow::RestOwnerSelector(0);
                 }
                 // Add a new person
-                KeyCode::Char('a') | KeyCode::Char('A') => {
+                Some(Action::AddPerson) => {
                     // Change Focused window to AddPerson
-                    app.focused = FocusedWindow::AddPerson(String::with_capacity(30));
+                    app.focused = FocusedWindow::AddPerson(TextInput::new());
+                }
+                // Enter the receipt's stated total
+                Some(Action::SetTotal) => {
+                    let receipt_idx = app.current_receipt();
+                    let current = app.data.receipts[receipt_idx].expected_total
+                        .map(|total| app.config.format.editable(total))
+                        .unwrap_or_default();
+                    app.focused = FocusedWindow::SetTotal(receipt_idx, TextInput::numeric().with_text(&current));
                 }
+                // Add a receipt-level charge
+                Some(Action::AddCharge) => {
+                    app.focused = FocusedWindow::AddCharge(app.current_receipt(), TextInput::new());
+                }
+                // Add a receipt-level voucher or loyalty credit
+                Some(Action::AddCredit) => {
+                    app.focused = FocusedWindow::AddCredit(app.current_receipt(), TextInput::new());
+                }
+                // Only receipts in a foreign currency need a rate
+                Some(Action::SetRate) => {
+                    let receipt_idx = app.current_receipt();
+                    let currency = &app.data.receipts[receipt_idx].currency;
+                    if *currency != app.data.settlement_currency {
+                        let current = app.data.rates
+                            .convert(1.0, currency, &app.data.settlement_currency)
+                            .map(|rate| app.config.format.rate(rate))
+                            .unwrap_or_default();
+                        app.focused = FocusedWindow::SetRate(receipt_idx, TextInput::numeric().with_text(&current));
+                    }
+                }
+                Some(Action::ScrollLeft) => {
+                    app.view.description_offset = app.view.description_offset.saturating_sub(SCROLL_STEP);
+                }
+                Some(Action::ScrollRight) => {
+                    let longest = app.data.items.iter()
+                        .map(|item| item.description.chars().count())
+                        .max()
+                        .unwrap_or(0);
+                    if app.view.description_offset + SCROLL_STEP < longest {
+                        app.view.description_offset += SCROLL_STEP;
+                    }
+                }
+                Some(Action::ToggleWrap) => {
+                    app.view.wrap_descriptions = !app.view.wrap_descriptions;
+                }
+                Some(Action::ToggleSidebar) => {
+                    app.view.sidebar_collapsed = !app.view.sidebar_collapsed;
+                }
+                Some(Action::Export) => export_split(app),
+                Some(Action::Save) => save_session(app),
+                Some(Action::NextPanel) => cycle_focus(app, true),
+                Some(Action::PrevPanel) => cycle_focus(app, false),
                 _ => {}
             },
-            Event::Tick => {}
+            Event::Paste(_) | Event::Resize(_,_) => {}
         }
     }
-    return false;
+    false
 }
 
 pub fn people_input_handler(event: &Event, app: &mut AppState) -> bool {
     if let FocusedWindow::People(idx) = &mut app.focused {
         match event {
-            Event::Input(event) => match event.code {
-                KeyCode::Char('q') | KeyCode::Char('Q') => {
-                    return true;
+            Event::Input(event) => match app.config.keymap.action(event) {
+                Some(Action::Quit) => return quit(app),
+                Some(Action::Down)
+                    if !app.data.people.is_empty() && *idx < app.data.people.len() - 1 =>
+                {
+                    *idx += 1;
                 }
-                KeyCode::Down | KeyCode::Char('j') => {
-                    if app.data.people.len() > 0 && *idx < app.data.people.len() - 1 {
-                        *idx += 1;
-                    }
+                Some(Action::Up) if *idx > 0usize => {
+                    *idx -= 1;
                 }
-                KeyCode::Up | KeyCode::Char('k') => {
-                    if *idx > 0usize {
-                        *idx -= 1;
-                    }
+                // Show what this person is paying for
+                Some(Action::Select) if *idx < app.data.people.len() => {
+                    app.focused = FocusedWindow::PersonDetail(*idx, 0);
+                }
+                Some(Action::AddPerson) => {
+                    app.focused = FocusedWindow::AddPerson(TextInput::new());
                 }
-                // KeyCode::Tab => {
-                //     app.focused = FocusedWindow::Items(0);
-                // }
+                // Remove this person, their items are handed to co-owners
+                Some(Action::Delete) if *idx < app.data.people.len() => {
+                    app.dialog = Some(Dialog {
+                        title: "Remove Person",
+                        message: format!(
+                            "Remove {}? Items they share go to the other owners, the rest become unowned.",
+                            app.data.people[*idx],
+                        ),
+                        confirm: "Remove",
+                        action: DialogAction::RemovePerson(*idx),
+                    });
+                }
+                // Toggle who paid the receipt
+                Some(Action::SetPayer) if *idx < app.data.people.len() => {
+                    let person_idx = *idx;
+                    let receipt_idx = app.current_receipt();
+                    let receipt = &mut app.data.receipts[receipt_idx];
+                    receipt.payer = if receipt.payer == Some(person_idx) { None } else { Some(person_idx) };
+                    app.status = Some(match receipt.payer {
+                        Some(payer) => format!("{} paid {}", app.data.people[payer], receipt.name),
+                        None => format!("Nobody paid {}", receipt.name),
+                    });
+                }
+                Some(Action::Export) => export_split(app),
+                Some(Action::Save) => save_session(app),
+                Some(Action::NextPanel) => cycle_focus(app, true),
+                Some(Action::PrevPanel) => cycle_focus(app, false),
                 _ => {}
             },
-            Event::Tick => {}
+            Event::Paste(_) | Event::Resize(_,_) => {}
         }
     }
-    return false;
+    false
 }
 
 
+pub fn charges_input_handler(event: &Event, app: &mut AppState) -> bool {
+    if let FocusedWindow::Charges(idx) = &mut app.focused {
+        match event {
+            Event::Input(event) => match app.config.keymap.action(event) {
+                Some(Action::Quit) => return quit(app),
+                Some(Action::Down)
+                    if !app.data.charges.is_empty() && *idx < app.data.charges.len() - 1 =>
+                {
+                    *idx += 1;
+                }
+                Some(Action::Up) if *idx > 0usize => {
+                    *idx -= 1;
+                }
+                // Change how this charge is split
+                Some(Action::Select) if *idx < app.data.charges.len() => {
+                    app.focused = FocusedWindow::ChargeSplitSelector(*idx, 0, vec![]);
+                }
+                Some(Action::AddCharge) => {
+                    app.focused = FocusedWindow::AddCharge(app.current_receipt(), TextInput::new());
+                }
+                Some(Action::AddCredit) => {
+                    app.focused = FocusedWindow::AddCredit(app.current_receipt(), TextInput::new());
+                }
+                Some(Action::Delete) if *idx < app.data.charges.len() => {
+                    app.dialog = Some(Dialog {
+                        title: "Remove Charge",
+                        message: format!("Remove {}?", app.data.charges[*idx].description),
+                        confirm: "Remove",
+                        action: DialogAction::RemoveCharge(*idx),
+                    });
+                }
+                Some(Action::Save) => save_session(app),
+                Some(Action::NextPanel) => cycle_focus(app, true),
+                Some(Action::PrevPanel) => cycle_focus(app, false),
+                _ => {}
+            },
+            Event::Paste(_) | Event::Resize(_,_) => {}
+        }
+    }
+    false
+}
+
 pub fn owner_selector_input_handler(event: &Event, app: &mut AppState) -> bool {
     if let FocusedWindow::OwnerSelector(item_idx, person_idx, owners_indices) = &mut app.focused {
         match event {
-            Event::Input(event) => match event.code {
-                KeyCode::Char('q') | KeyCode::Char('Q') => {
-                    return true;
+            Event::Input(event) => match app.config.keymap.action(event) {
+                Some(Action::Quit) => return quit(app),
+                Some(Action::Down)
+                    if !app.data.people.is_empty() && *person_idx < app.data.people.len() - 1 =>
+                {
+                    *person_idx += 1;
                 }
-                KeyCode::Down | KeyCode::Char('j') | KeyCode::Char('J') => {
-                    if app.data.people.len() > 0 && *person_idx < app.data.people.len() - 1 {
-                        *person_idx += 1;
-                    }
-                }
-                KeyCode::Up | KeyCode::Char('k') | KeyCode::Char('K') => {
-                    if *person_idx > 0usize {
-                        *person_idx -= 1;
-                    }
+                Some(Action::Up) if *person_idx > 0usize => {
+                    *person_idx -= 1;
                 }
-                KeyCode::Enter => {
+                Some(Action::Select) => {
                     if *person_idx < app.data.people.len() {
                         owners_indices.push(*person_idx);
-                        let mut owners = from_indices_to_owners(&owners_indices);
+                        let mut owners = from_indices_to_owners(owners_indices);
                         flatten_owners(&mut owners);
                         app.data.set_item_owners(*item_idx, owners);
                     }
                     app.focused = FocusedWindow::Items(*item_idx);
                 }
-                KeyCode::Char('p') | KeyCode::Char('P') => {
-                    if *person_idx < app.data.people.len() {
-                        owners_indices.push(*person_idx);
-                    }
+                Some(Action::PickPerson) if *person_idx < app.data.people.len() => {
+                    owners_indices.push(*person_idx);
                 }
-                KeyCode::Esc => {
+                Some(Action::Cancel) => {
                     app.focused = FocusedWindow::Items(*item_idx);
                 }
                 _ => {}
             },
-            Event::Tick => {}
+            Event::Paste(_) | Event::Resize(_,_) => {}
         }
     }
-    return false;
+    false
 }
 
 pub fn rest_owner_selector_input_handler(event: &Event, app: &mut AppState) -> bool {
     if let FocusedWindow::RestOwnerSelector(person_idx) = &mut app.focused {
         match event {
-            Event::Input(event) => match event.code {
-                KeyCode::Char('q') | KeyCode::Char('Q') => {
-                    return true;
+            Event::Input(event) => match app.config.keymap.action(event) {
+                Some(Action::Quit) => return quit(app),
+                Some(Action::Down)
+                    if !app.data.people.is_empty() && *person_idx < app.data.people.len() - 1 =>
+                {
+                    *person_idx += 1;
+                }
+                Some(Action::Up) if *person_idx > 0usize => {
+                    *person_idx -= 1;
+                }
+                Some(Action::Select) => {
+                    let unowned = app.data.items.iter().filter(|item| item.owners.is_empty()).count();
+                    if *person_idx < app.data.people.len() && unowned > 0 {
+                        app.dialog = Some(Dialog {
+                            title: "Set Owner",
+                            message: format!(
+                                "Give the {} unowned item{} to {}?",
+                                unowned,
+                                if unowned == 1 { "" } else { "s" },
+                                app.data.people[*person_idx],
+                            ),
+                            confirm: "Assign",
+                            action: DialogAction::AssignRest(*person_idx),
+                        });
+                    }
+                    else {
+                        app.return_to_panel();
+                    }
+                }
+                Some(Action::Cancel) => {
+                    app.return_to_panel();
+                }
+                _ => {}
+            },
+            Event::Paste(_) | Event::Resize(_,_) => {}
+        }
+    }
+    false
+}
+
+pub fn add_person_input_handler(event: &Event, app: &mut AppState) -> bool {
+    if let FocusedWindow::AddPerson(input) = &mut app.focused {
+        match event {
+            Event::Input(key) if input.handle(key) => {}
+            Event::Input(key) => match app.config.keymap.action(key) {
+                Some(Action::Select) => {
+                    let name = input.text().trim();
+                    if name.is_empty() {
+                        input.error = Some("The name can't be empty".into());
+                    }
+                    else if app.data.people.iter().any(|person| person.to_lowercase() == name.to_lowercase()) {
+                        input.error = Some(format!("{} is already on the list", name));
+                    }
+                    else {
+                        app.data.people.push(name.to_string());
+                        app.return_to_panel();
+                    }
+                }
+                Some(Action::Cancel) => {
+                    app.return_to_panel();
+                }
+                _ => {}
+            },
+            Event::Paste(text) => input.insert(text),
+            Event::Resize(_,_) => {}
+        }
+    }
+    false
+}
+
+pub fn set_total_input_handler(event: &Event, app: &mut AppState) -> bool {
+    if let FocusedWindow::SetTotal(receipt_idx, input) = &mut app.focused {
+        match event {
+            Event::Input(key) if input.handle(key) => {}
+            Event::Input(key) => match app.config.keymap.action(key) {
+                Some(Action::Select) => {
+                    // An empty prompt clears the expected total
+                    if input.text().trim().is_empty() {
+                        app.data.receipts[*receipt_idx].expected_total = None;
+                        app.return_to_panel();
+                    }
+                    else if let Some(value) = parse_price(input.text()) {
+                        app.data.receipts[*receipt_idx].expected_total = Some(value);
+                        app.return_to_panel();
+                    }
+                    else {
+                        input.error = Some("Not an amount".into());
+                    }
+                }
+                Some(Action::Cancel) => {
+                    app.return_to_panel();
+                }
+                _ => {}
+            },
+            Event::Paste(text) => input.insert(text),
+            Event::Resize(_,_) => {}
+        }
+    }
+    false
+}
+
+// Units of the settlement currency one unit of the receipt's is worth
+pub fn set_rate_input_handler(event: &Event, app: &mut AppState) -> bool {
+    if let FocusedWindow::SetRate(receipt_idx, input) = &mut app.focused {
+        match event {
+            Event::Input(key) if input.handle(key) => {}
+            Event::Input(key) => match app.config.keymap.action(key) {
+                Some(Action::Select) => {
+                    let currency = &app.data.receipts[*receipt_idx].currency;
+                    match parse_price(input.text()).filter(|value| *value > 0.0) {
+                        // Rates are relative to the table's base, which
+                        // has to be one of the two
+                        Some(value) if !app.data.rates.set(currency, &app.data.settlement_currency, value) => {
+                            input.error = Some(format!(
+                                "Neither {} nor {} is in the rates table",
+                                currency,
+                                app.data.settlement_currency,
+                            ));
+                        }
+                        Some(_) => app.return_to_panel(),
+                        None => input.error = Some("The rate must be above 0".into()),
+                    }
+                }
+                Some(Action::Cancel) => {
+                    app.return_to_panel();
+                }
+                _ => {}
+            },
+            Event::Paste(text) => input.insert(text),
+            Event::Resize(_,_) => {}
+        }
+    }
+    false
+}
+
+// Validates and stores one field of an item
+fn set_item_field(item: &mut Item, field: ItemField, text: &str) -> Result<(), String> {
+    let text = text.trim();
+    match field {
+        ItemField::Description if text.is_empty() => return Err("The description can't be empty".into()),
+        ItemField::Description => item.description = text.to_string(),
+        ItemField::Quantity => {
+            item.quantity = parse_price(text)
+                .filter(|quantity| *quantity > 0.0)
+                .ok_or("The quantity must be above 0")?;
+        }
+        ItemField::Unit => item.unit = text.to_string(),
+        ItemField::Price => {
+            item.price = parse_price(text)
+                .filter(|price| *price >= 0.0)
+                .ok_or("Not a price")?;
+        }
+        ItemField::Category => item.category = text.to_string(),
+    }
+    Ok(())
+}
+
+pub fn edit_item_input_handler(event: &Event, app: &mut AppState) -> bool {
+    if let FocusedWindow::EditItem(item_idx, field, input) = &mut app.focused {
+        match event {
+            Event::Input(key) if input.handle(key) => {}
+            Event::Input(key) => match app.config.keymap.action(key) {
+                // Each field is saved as it's left, totals follow right away
+                Some(action @ (Action::Select | Action::NextPanel | Action::PrevPanel)) => {
+                    let (item_idx, field) = (*item_idx, *field);
+                    if let Err(err) = set_item_field(&mut app.data.items[item_idx], field, input.text()) {
+                        input.error = Some(err.to_string());
+                    }
+                    else if action == Action::Select {
+                        app.focused = FocusedWindow::Items(item_idx);
+                    }
+                    else {
+                        let next = field.cycle(action == Action::NextPanel);
+                        let input = app.item_field_input(item_idx, next);
+                        app.focused = FocusedWindow::EditItem(item_idx, next, input);
+                    }
+                }
+                Some(Action::Cancel) => {
+                    app.focused = FocusedWindow::Items(*item_idx);
+                }
+                _ => {}
+            },
+            Event::Paste(text) => input.insert(text),
+            Event::Resize(_,_) => {}
+        }
+    }
+    false
+}
+
+// Parses "<description> <amount>", e.g. "Entrega 3,99"
+fn parse_described_amount(text: &str) -> Option<(&str, f32)> {
+    let (description, amount) = text.trim().rsplit_once(' ')?;
+    Some((description.trim(), parse_price(amount)?))
+}
+
+pub fn add_item_input_handler(event: &Event, app: &mut AppState) -> bool {
+    if let FocusedWindow::AddItem(receipt_idx, input) = &mut app.focused {
+        match event {
+            Event::Input(key) if input.handle(key) => {}
+            Event::Input(key) => match app.config.keymap.action(key) {
+                Some(Action::Select) => {
+                    match parse_described_amount(input.text()).filter(|(_, price)| *price >= 0.0) {
+                        Some((description, price)) if !description.is_empty() => {
+                            let item_idx = app.data.add_item(Item {
+                                receipt: *receipt_idx,
+                                description: description.to_string(),
+                                category: String::new(),
+                                quantity: 1.0,
+                                unit: String::new(),
+                                price,
+                                owners: Vec::new(),
+                                flagged: false,
+                                manual: true,
+                            });
+                            app.focused = FocusedWindow::Items(item_idx);
+                        }
+                        _ => input.error = Some("Expected a description and a price, e.g. Pão 1,20".into()),
+                    }
+                }
+                Some(Action::Cancel) => {
+                    app.return_to_panel();
                 }
-                KeyCode::Down | KeyCode::Char('j') | KeyCode::Char('J') => {
-                    if app.data.people.len() > 0 && *person_idx < app.data.people.len() - 1 {
-                        *person_idx += 1;
+                _ => {}
+            },
+            Event::Paste(text) => input.insert(text),
+            Event::Resize(_,_) => {}
+        }
+    }
+    false
+}
+
+// Quantity moved to a new line, e.g. one of two yoghurt packs
+pub fn split_item_input_handler(event: &Event, app: &mut AppState) -> bool {
+    if let FocusedWindow::SplitItem(item_idx, input) = &mut app.focused {
+        match event {
+            Event::Input(key) if input.handle(key) => {}
+            Event::Input(key) => match app.config.keymap.action(key) {
+                Some(Action::Select) => {
+                    let item_idx = *item_idx;
+                    match parse_price(input.text()).and_then(|quantity| app.data.split_item(item_idx, quantity)) {
+                        Some(part_idx) => app.focused = FocusedWindow::Items(part_idx),
+                        None => input.error = Some(format!(
+                            "Expected less than {}",
+                            app.config.format.quantity(app.data.items[item_idx].quantity, &app.data.items[item_idx].unit),
+                        )),
+                    }
+                }
+                Some(Action::Cancel) => {
+                    app.focused = FocusedWindow::Items(*item_idx);
+                }
+                _ => {}
+            },
+            Event::Paste(text) => input.insert(text),
+            Event::Resize(_,_) => {}
+        }
+    }
+    false
+}
+
+pub fn import_receipt_input_handler(event: &Event, app: &mut AppState) -> bool {
+    if let FocusedWindow::ImportReceipt(input) = &mut app.focused {
+        match event {
+            Event::Input(key) if input.handle(key) => {}
+            Event::Input(key) => match app.config.keymap.action(key) {
+                Some(Action::Select) => {
+                    let path = PathBuf::from(input.text().trim());
+                    if path.as_os_str().is_empty() {
+                        input.error = Some("Expected the path of a receipt or session".into());
+                        return false;
+                    }
+                    match app.import(&path) {
+                        Ok(receipt_idx) => {
+                            let item_idx = app.data.items.iter()
+                                .position(|item| item.receipt == receipt_idx)
+                                .unwrap_or(0);
+                            app.status = Some(format!("Imported {}", path.display()));
+                            app.focused = FocusedWindow::Items(item_idx);
+                        }
+                        Err(err) => {
+                            if let FocusedWindow::ImportReceipt(input) = &mut app.focused {
+                                input.error = Some(err);
+                            }
+                        }
                     }
                 }
-                KeyCode::Up | KeyCode::Char('k') | KeyCode::Char('K') => {
-                    if *person_idx > 0usize {
-                        *person_idx -= 1;
+                Some(Action::Cancel) => {
+                    app.return_to_panel();
+                }
+                _ => {}
+            },
+            Event::Paste(text) => input.insert(text),
+            Event::Resize(_,_) => {}
+        }
+    }
+    false
+}
+
+pub fn add_charge_input_handler(event: &Event, app: &mut AppState) -> bool {
+    let is_credit = matches!(app.focused, FocusedWindow::AddCredit(_,_));
+    if let FocusedWindow::AddCharge(receipt_idx, input) | FocusedWindow::AddCredit(receipt_idx, input) = &mut app.focused {
+        match event {
+            Event::Input(key) if input.handle(key) => {}
+            Event::Input(key) => match app.config.keymap.action(key) {
+                Some(Action::Select) => {
+                    match parse_described_amount(input.text()) {
+                        Some((description, amount)) if !description.is_empty() => {
+                            // Credits are stored negative and split by subtotal unless chosen otherwise
+                            let charge = if is_credit {
+                                Charge {
+                                    receipt: *receipt_idx,
+                                    description: description.to_string(),
+                                    amount: -amount.abs(),
+                                    split: SplitPolicy::Proportional,
+                                    manual: true,
+                                }
+                            }
+                            else {
+                                Charge {
+                                    receipt: *receipt_idx,
+                                    description: description.to_string(),
+                                    amount,
+                                    split: SplitPolicy::Equal,
+                                    manual: true,
+                                }
+                            };
+                            let charge_idx = app.data.add_charge(charge);
+                            app.focused = FocusedWindow::ChargeSplitSelector(charge_idx, 0, vec![]);
+                        }
+                        _ => input.error = Some("Expected a description and an amount, e.g. Entrega 3,99".into()),
                     }
                 }
-                KeyCode::Enter => {
+                Some(Action::Cancel) => {
+                    app.return_to_panel();
+                }
+                _ => {}
+            },
+            Event::Paste(text) => input.insert(text),
+            Event::Resize(_,_) => {}
+        }
+    }
+    false
+}
+
+pub fn charge_split_selector_input_handler(event: &Event, app: &mut AppState) -> bool {
+    if let FocusedWindow::ChargeSplitSelector(charge_idx, person_idx, people_indices) = &mut app.focused {
+        match event {
+            Event::Input(event) => match app.config.keymap.action(event) {
+                Some(Action::Quit) => return quit(app),
+                Some(Action::Down)
+                    if !app.data.people.is_empty() && *person_idx < app.data.people.len() - 1 =>
+                {
+                    *person_idx += 1;
+                }
+                Some(Action::Up) if *person_idx > 0usize => {
+                    *person_idx -= 1;
+                }
+                // Split equally between everyone
+                Some(Action::SplitEqual) => {
+                    app.data.set_charge_split(*charge_idx, SplitPolicy::Equal);
+                    app.focused = FocusedWindow::Charges(*charge_idx);
+                }
+                // Split proportionally to each person's subtotal
+                Some(Action::SplitProportional) => {
+                    app.data.set_charge_split(*charge_idx, SplitPolicy::Proportional);
+                    app.focused = FocusedWindow::Charges(*charge_idx);
+                }
+                // Split between the chosen people
+                Some(Action::Select) => {
                     if *person_idx < app.data.people.len() {
-                        app.data.set_rest_items_owner(*person_idx);
+                        people_indices.push(*person_idx);
+                        people_indices.sort_unstable();
+                        people_indices.dedup();
+                        let people = std::mem::take(people_indices);
+                        app.data.set_charge_split(*charge_idx, SplitPolicy::People(people));
                     }
-                    app.focused = FocusedWindow::Items(0);
+                    app.focused = FocusedWindow::Charges(*charge_idx);
+                }
+                Some(Action::PickPerson) if *person_idx < app.data.people.len() => {
+                    people_indices.push(*person_idx);
                 }
-                KeyCode::Esc => {
-                    app.focused = FocusedWindow::Items(0);
+                Some(Action::Cancel) => {
+                    app.focused = FocusedWindow::Charges(*charge_idx);
                 }
                 _ => {}
             },
-            Event::Tick => {}
+            Event::Paste(_) | Event::Resize(_,_) => {}
         }
     }
-    return false;
+    false
 }
 
-pub fn add_person_input_handler(event: &Event, app: &mut AppState) -> bool {
-    if let FocusedWindow::AddPerson(name) = &mut app.focused {
+pub fn person_detail_input_handler(event: &Event, app: &mut AppState) -> bool {
+    if let FocusedWindow::PersonDetail(person_idx, row) = &mut app.focused {
         match event {
-            Event::Input(event) => match event.code {
-                KeyCode::Char(c) => {
-                    name.push(c);
-                }
-                KeyCode::Backspace => {
-                    use substring::Substring;
-                    if !name.is_empty() {
-                        // if event.modifiers.contains(KeyModifiers::CONTROL) {
-                        //     name.clear();
-                        // }
-                        // else {
-                            name.pop();
-                            
-                            // *name = String::from(name.substring(0, name.len()-1))
-                        // }
-                    }
-                }
-                KeyCode::Enter => {
-                    // if name.len() > 0 {
-                        app.data.people.push(name.clone());
-                        app.focused = FocusedWindow::Items(0);
-                    // }
-                }
-                KeyCode::Esc => {
-                    app.focused = FocusedWindow::Items(0);
+            Event::Input(event) => match app.config.keymap.action(event) {
+                Some(Action::Quit) => return quit(app),
+                Some(Action::Down)
+                    if *row + 1 < app.data.person_breakdown(*person_idx).lines() =>
+                {
+                    *row += 1;
+                }
+                Some(Action::Up) if *row > 0usize => {
+                    *row -= 1;
+                }
+                Some(Action::Select) | Some(Action::Cancel) => {
+                    app.focused = FocusedWindow::People(*person_idx);
                 }
                 _ => {}
             },
-            Event::Tick => {}
+            Event::Paste(_) | Event::Resize(_,_) => {}
         }
     }
-    return false;
-}
\ No newline at end of file
+    false
+}
@@ -0,0 +1,320 @@
+use crate::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::HashMap;
+use std::fmt;
+
+// Everything a key can be bound to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Quit,
+    Up,
+    Down,
+    Select,
+    Cancel,
+    NextPanel,
+    PrevPanel,
+    AddPerson,
+    AssignRest,
+    PickPerson,
+    Delete,
+    EditItem,
+    AddItem,
+    DuplicateItem,
+    SplitItem,
+    Import,
+    SetTotal,
+    AddCharge,
+    AddCredit,
+    SetRate,
+    SetPayer,
+    Export,
+    Save,
+    SplitEqual,
+    SplitProportional,
+    ScrollLeft,
+    ScrollRight,
+    ToggleWrap,
+    ToggleSidebar,
+    Help,
+}
+
+impl Action {
+    pub const ALL: [Action; 30] = [
+        Action::Quit,
+        Action::Up,
+        Action::Down,
+        Action::Select,
+        Action::Cancel,
+        Action::NextPanel,
+        Action::PrevPanel,
+        Action::AddPerson,
+        Action::AssignRest,
+        Action::PickPerson,
+        Action::Delete,
+        Action::EditItem,
+        Action::AddItem,
+        Action::DuplicateItem,
+        Action::SplitItem,
+        Action::Import,
+        Action::SetTotal,
+        Action::AddCharge,
+        Action::AddCredit,
+        Action::SetRate,
+        Action::SetPayer,
+        Action::Export,
+        Action::Save,
+        Action::SplitEqual,
+        Action::SplitProportional,
+        Action::ScrollLeft,
+        Action::ScrollRight,
+        Action::ToggleWrap,
+        Action::ToggleSidebar,
+        Action::Help,
+    ];
+
+    // Name used in the config file
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::Up => "up",
+            Action::Down => "down",
+            Action::Select => "select",
+            Action::Cancel => "cancel",
+            Action::NextPanel => "next_panel",
+            Action::PrevPanel => "prev_panel",
+            Action::AddPerson => "add_person",
+            Action::AssignRest => "assign_rest",
+            Action::PickPerson => "pick_person",
+            Action::Delete => "delete",
+            Action::EditItem => "edit_item",
+            Action::AddItem => "add_item",
+            Action::DuplicateItem => "duplicate_item",
+            Action::SplitItem => "split_item",
+            Action::Import => "import",
+            Action::SetTotal => "set_total",
+            Action::AddCharge => "add_charge",
+            Action::AddCredit => "add_credit",
+            Action::SetRate => "set_rate",
+            Action::SetPayer => "set_payer",
+            Action::Export => "export",
+            Action::Save => "save",
+            Action::SplitEqual => "split_equal",
+            Action::SplitProportional => "split_proportional",
+            Action::ScrollLeft => "scroll_left",
+            Action::ScrollRight => "scroll_right",
+            Action::ToggleWrap => "toggle_wrap",
+            Action::ToggleSidebar => "toggle_sidebar",
+            Action::Help => "help",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.iter().copied().find(|action| action.name() == name)
+    }
+}
+
+// A key together with the modifiers that matter for bindings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Key {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl Key {
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        // Shift is already part of the character (or of BackTab)
+        let modifiers = modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT);
+        Key { code, modifiers }
+    }
+
+    // Parses "q", "enter", "ctrl-w", "shift-tab"...
+    pub fn parse(text: &str) -> Option<Key> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = text;
+        loop {
+            let lower = rest.to_lowercase();
+            if lower.starts_with("ctrl-") {
+                modifiers |= KeyModifiers::CONTROL;
+            }
+            else if lower.starts_with("alt-") {
+                modifiers |= KeyModifiers::ALT;
+            }
+            else if lower.starts_with("shift-") {
+                modifiers |= KeyModifiers::SHIFT;
+            }
+            else {
+                break;
+            }
+            rest = &rest[rest.find('-').unwrap() + 1..];
+        }
+
+        let code = match rest.to_lowercase().as_str() {
+            "enter" | "return" => KeyCode::Enter,
+            "esc" | "escape" => KeyCode::Esc,
+            "tab" if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "backspace" => KeyCode::Backspace,
+            "delete" | "del" => KeyCode::Delete,
+            "insert" => KeyCode::Insert,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            "space" => KeyCode::Char(' '),
+            name if name.len() > 1 && name.starts_with('f') => {
+                KeyCode::F(name[1..].parse().ok()?)
+            }
+            _ => {
+                let mut chars = rest.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => KeyCode::Char(c),
+                    _ => return None,
+                }
+            }
+        };
+        Some(Key::new(code, modifiers))
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "Ctrl-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "Alt-")?;
+        }
+        match self.code {
+            KeyCode::Enter => write!(f, "Enter"),
+            KeyCode::Esc => write!(f, "Esc"),
+            KeyCode::Tab => write!(f, "Tab"),
+            KeyCode::BackTab => write!(f, "Shift-Tab"),
+            KeyCode::Backspace => write!(f, "Backspace"),
+            KeyCode::Delete => write!(f, "Del"),
+            KeyCode::Insert => write!(f, "Ins"),
+            KeyCode::Up => write!(f, "↑"),
+            KeyCode::Down => write!(f, "↓"),
+            KeyCode::Left => write!(f, "←"),
+            KeyCode::Right => write!(f, "→"),
+            KeyCode::Home => write!(f, "Home"),
+            KeyCode::End => write!(f, "End"),
+            KeyCode::PageUp => write!(f, "PgUp"),
+            KeyCode::PageDown => write!(f, "PgDn"),
+            KeyCode::F(n) => write!(f, "F{}", n),
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+        }
+    }
+}
+
+pub struct Keymap {
+    bindings: HashMap<Key, Action>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap::preset("default").unwrap()
+    }
+}
+
+impl Keymap {
+    // Bindings shared by every preset
+    const COMMON: [(&'static str, Action); 28] = [
+        ("q", Action::Quit),
+        ("enter", Action::Select),
+        ("esc", Action::Cancel),
+        ("tab", Action::NextPanel),
+        ("shift-tab", Action::PrevPanel),
+        ("a", Action::AddPerson),
+        ("r", Action::AssignRest),
+        ("p", Action::PickPerson),
+        ("d", Action::Delete),
+        ("i", Action::EditItem),
+        ("n", Action::AddItem),
+        ("y", Action::DuplicateItem),
+        ("/", Action::SplitItem),
+        ("f", Action::Import),
+        ("t", Action::SetTotal),
+        ("c", Action::AddCharge),
+        ("v", Action::AddCredit),
+        ("x", Action::SetRate),
+        ("$", Action::SetPayer),
+        ("o", Action::Export),
+        ("ctrl-s", Action::Save),
+        ("e", Action::SplitEqual),
+        ("s", Action::SplitProportional),
+        ("delete", Action::Delete),
+        ("ctrl-c", Action::Quit),
+        ("w", Action::ToggleWrap),
+        ("z", Action::ToggleSidebar),
+        ("?", Action::Help),
+    ];
+
+    // "vim" navigates with j/k, "arrows" with the arrow keys and
+    // "default" accepts both
+    pub fn preset(name: &str) -> Option<Self> {
+        let navigation: &[(&str, Action)] = match name {
+            "default" => &[
+                ("j", Action::Down), ("k", Action::Up),
+                ("h", Action::ScrollLeft), ("l", Action::ScrollRight),
+                ("down", Action::Down), ("up", Action::Up),
+                ("left", Action::ScrollLeft), ("right", Action::ScrollRight),
+            ],
+            "vim" => &[
+                ("j", Action::Down), ("k", Action::Up),
+                ("h", Action::ScrollLeft), ("l", Action::ScrollRight),
+            ],
+            "arrows" => &[
+                ("down", Action::Down), ("up", Action::Up),
+                ("left", Action::ScrollLeft), ("right", Action::ScrollRight),
+            ],
+            _ => return None,
+        };
+
+        let mut keymap = Keymap { bindings: HashMap::new() };
+        for (key, action) in Keymap::COMMON.iter().chain(navigation) {
+            keymap.bind(Key::parse(key).unwrap(), *action);
+        }
+        Some(keymap)
+    }
+
+    pub fn bind(&mut self, key: Key, action: Action) {
+        self.bindings.insert(key, action);
+    }
+
+    // Removes every key bound to this action
+    pub fn unbind(&mut self, action: Action) {
+        self.bindings.retain(|_, bound| *bound != action);
+    }
+
+    pub fn action(&self, event: &KeyEvent) -> Option<Action> {
+        let key = Key::new(event.code, event.modifiers);
+        self.bindings.get(&key).copied().or_else(|| {
+            // Letters are bound case insensitively
+            match key.code {
+                KeyCode::Char(c) if c.is_uppercase() => self.bindings
+                    .get(&Key { code: KeyCode::Char(c.to_ascii_lowercase()), ..key })
+                    .copied(),
+                _ => None,
+            }
+        })
+    }
+
+    // Keys bound to an action, sorted for display
+    pub fn keys(&self, action: Action) -> Vec<Key> {
+        let mut keys: Vec<Key> = self.bindings.iter()
+            .filter(|(_, bound)| **bound == action)
+            .map(|(key, _)| *key)
+            .collect();
+        // Plain single character keys first
+        keys.sort_by_key(|key| {
+            let name = key.to_string();
+            (!key.modifiers.is_empty(), name.chars().count() > 1, name)
+        });
+        keys
+    }
+}
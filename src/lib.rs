@@ -0,0 +1,30 @@
+//! Splits grocery receipts between the people who bought each item.
+//!
+//! The model, receipt parser, split computation, sessions and exports work
+//! without a terminal. The interactive front-end is behind the default
+//! `tui` feature.
+
+pub mod model;
+pub mod parser;
+pub mod currency;
+pub mod format;
+pub mod session;
+pub mod export;
+pub mod autosave;
+
+#[cfg(feature = "tui")]
+pub mod event;
+#[cfg(feature = "tui")]
+pub mod state;
+#[cfg(feature = "tui")]
+pub mod ui;
+#[cfg(feature = "tui")]
+pub mod input_handlers;
+#[cfg(feature = "tui")]
+pub mod keymap;
+#[cfg(feature = "tui")]
+pub mod config;
+#[cfg(feature = "tui")]
+pub mod theme;
+#[cfg(feature = "tui")]
+pub mod text_input;
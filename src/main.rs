@@ -1,118 +1,378 @@
-use crossterm::{
-    event::{
-        self, DisableMouseCapture, /* EnableMouseCapture, */ Event as CEvent,
-    },
-    execute,
-    terminal::{
-        disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen
-    },
+use crossterm::event::{
+    self, Event as CEvent,
+    KeyCode as CKeyCode, KeyEvent as CKeyEvent, KeyModifiers as CKeyModifiers,
 };
 use std::{
-    io,
+    fs, io,
+    path::{Path, PathBuf},
+    process::ExitCode,
     thread,
-    time::Duration,
-    sync::mpsc,
-    time::Instant,
-};
-use tui::{
-    backend::CrosstermBackend,
-    Terminal,
+    time::{Duration, SystemTime},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
 };
 
 /////////////////////////////////////
 
-pub mod state;
-use state::*;
-pub mod ui;
-use ui::*;
-pub mod input_handlers;
-use input_handlers::*;
+mod cli;
+mod terminal;
+
+use terminal::TerminalGuard;
+
+use konta::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use konta::state::*;
+use konta::ui::*;
+use konta::input_handlers::*;
+use konta::config::{self, Config};
+use konta::currency::ExchangeRates;
+use konta::model::Data;
+use konta::{autosave, export, parser, session};
 
 /////////////////////////////////////
 
+// Translates crossterm's keys, ignoring those the app has no use for
+fn key_event(key: CKeyEvent) -> Option<KeyEvent> {
+    let code = match key.code {
+        CKeyCode::Char(c) => KeyCode::Char(c),
+        CKeyCode::Enter => KeyCode::Enter,
+        CKeyCode::Esc => KeyCode::Esc,
+        CKeyCode::Tab => KeyCode::Tab,
+        CKeyCode::BackTab => KeyCode::BackTab,
+        CKeyCode::Backspace => KeyCode::Backspace,
+        CKeyCode::Delete => KeyCode::Delete,
+        CKeyCode::Insert => KeyCode::Insert,
+        CKeyCode::Up => KeyCode::Up,
+        CKeyCode::Down => KeyCode::Down,
+        CKeyCode::Left => KeyCode::Left,
+        CKeyCode::Right => KeyCode::Right,
+        CKeyCode::Home => KeyCode::Home,
+        CKeyCode::End => KeyCode::End,
+        CKeyCode::PageUp => KeyCode::PageUp,
+        CKeyCode::PageDown => KeyCode::PageDown,
+        CKeyCode::F(n) => KeyCode::F(n),
+        _ => return None,
+    };
+    let mut modifiers = KeyModifiers::NONE;
+    if key.modifiers.contains(CKeyModifiers::SHIFT) {
+        modifiers |= KeyModifiers::SHIFT;
+    }
+    if key.modifiers.contains(CKeyModifiers::CONTROL) {
+        modifiers |= KeyModifiers::CONTROL;
+    }
+    if key.modifiers.contains(CKeyModifiers::ALT) {
+        modifiers |= KeyModifiers::ALT;
+    }
+    Some(KeyEvent::new(code, modifiers))
+}
+
+fn is_printable(key: &KeyEvent) -> bool {
+    matches!(key.code, KeyCode::Char(_))
+        && !key.modifiers.contains(KeyModifiers::CONTROL)
+        && !key.modifiers.contains(KeyModifiers::ALT)
+}
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    use std::env;
-    use std::path::Path;
-    let args: Vec<String> = env::args().collect();
-    if args.len() < 2 || !Path::new(&args[1]).exists() {
-        eprintln!("Error: missing argument <file>");
-        return Ok(());
+// Printable keys already queued behind a printable key came faster than
+// anyone types, so they're sent as one paste. Also returns the key that
+// ended it, if any.
+fn read_paste(first: KeyEvent) -> (Event, Option<KeyEvent>) {
+    let mut text = String::new();
+    if let KeyCode::Char(c) = first.code {
+        text.push(c);
+    }
+    let mut rest = None;
+    while rest.is_none() && event::poll(Duration::ZERO).unwrap_or(false) {
+        match event::read() {
+            Ok(CEvent::Key(key)) => match key_event(key) {
+                Some(KeyEvent { code: KeyCode::Char(c), modifiers })
+                    if !modifiers.contains(KeyModifiers::CONTROL) && !modifiers.contains(KeyModifiers::ALT) =>
+                {
+                    text.push(c);
+                }
+                key => rest = key,
+            },
+            Ok(_) => {}
+            Err(_) => break,
+        }
+    }
+    if text.chars().count() > 1 {
+        (Event::Paste(text), rest)
+    }
+    else {
+        (Event::Input(first), rest)
     }
+}
 
-    // 1. Setup input Receiver thread
-    let (tx, rx) = mpsc::channel();
-    let tick_rate = Duration::from_millis(200);
+// What the main loop waits for
+enum Message {
+    Input(Event),
+    // The terminal can't be read anymore
+    Closed,
+    // A receipt parsed again after its file changed
+    Reloaded(usize, io::Result<Data>),
+}
+
+// How long the input thread waits for events before checking whether
+// it should stop
+const INPUT_POLL: Duration = Duration::from_millis(100);
+
+// Reads terminal events until `running` is cleared or the app stops
+// listening
+fn spawn_input_thread(tx: mpsc::Sender<Message>, running: Arc<AtomicBool>) -> thread::JoinHandle<()> {
     thread::spawn(move || {
-        let mut last_tick = Instant::now();
-        loop {
-            let timeout = tick_rate
-                .checked_sub(last_tick.elapsed())
-                .unwrap_or_else(|| Duration::from_secs(0));
-
-            if event::poll(timeout).expect("poll works") {
-                if let CEvent::Key(key) = event::read().expect("can read events") {
-                    tx.send(Event::Input(key)).expect("can send events");
-                }
+        while running.load(Ordering::Relaxed) {
+            match event::poll(INPUT_POLL) {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(_) => break,
             }
+            // Stop once the app is gone or the terminal can't be read
+            let sent = match event::read() {
+                Ok(CEvent::Key(key)) => match key_event(key) {
+                    Some(key) if is_printable(&key) => {
+                        let (event, rest) = read_paste(key);
+                        tx.send(Message::Input(event)).is_ok()
+                            && rest.is_none_or(|rest| tx.send(Message::Input(Event::Input(rest))).is_ok())
+                    }
+                    Some(key) => tx.send(Message::Input(Event::Input(key))).is_ok(),
+                    None => true,
+                },
+                Ok(CEvent::Resize(width, height)) => tx.send(Message::Input(Event::Resize(width, height))).is_ok(),
+                Ok(_) => true,
+                Err(_) => false,
+            };
+            if !sent {
+                break;
+            }
+        }
+        // Only heard if the terminal went away first
+        let _ = tx.send(Message::Closed);
+    })
+}
 
-            if last_tick.elapsed() >= tick_rate {
-                if tx.send(Event::Tick).is_ok() {
-                    last_tick = Instant::now();
+// How often the receipt is checked for changes
+const WATCH_POLL: Duration = Duration::from_millis(250);
+
+// Parses a receipt again whenever its file changes, until `running` is
+// cleared. Editors may write in several steps, so a change is only
+// picked up once the file stays the same for a whole poll.
+fn spawn_watch_thread(
+    receipt_idx: usize,
+    path: PathBuf,
+    currency: String,
+    tx: mpsc::Sender<Message>,
+    running: Arc<AtomicBool>,
+) -> thread::JoinHandle<()> {
+    let stamp = |path: &Path| -> Option<(SystemTime, u64)> {
+        let metadata = fs::metadata(path).ok()?;
+        Some((metadata.modified().ok()?, metadata.len()))
+    };
+    thread::spawn(move || {
+        let mut loaded = stamp(&path);
+        let mut last = loaded;
+        while running.load(Ordering::Relaxed) {
+            thread::sleep(WATCH_POLL);
+            let current = stamp(&path);
+            if current != last {
+                last = current;
+                continue;
+            }
+            // Gone for now, e.g. while being replaced
+            if current.is_some() && current != loaded {
+                loaded = current;
+                if tx.send(Message::Reloaded(receipt_idx, parser::load(&path, &currency))).is_err() {
+                    break;
                 }
             }
         }
+    })
+}
+
+fn main() -> ExitCode {
+    use std::env;
+    let args = match cli::Args::parse(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("Error: {}\n\n{}", err, cli::USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+    if args.help {
+        println!("{}", cli::USAGE);
+        return ExitCode::SUCCESS;
+    }
+    let (file, others) = match args.files.split_first() {
+        Some((file, others)) => (file.clone(), others),
+        None => {
+            eprintln!("Error: missing argument <file>\n\n{}", cli::USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    // Receipts are in the home currency unless told otherwise
+    let currency = args.currency.clone().unwrap_or_else(|| config.format.currency.clone());
+    let mut data = match session::load_file(&file, &currency) {
+        Ok(data) => data,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+    // Saved sessions bring their own currencies and rates
+    if !session::is_session(&file) {
+        data.settlement_currency = config.format.currency.clone();
+    }
+    if let Some(settle) = args.settle {
+        data.settlement_currency = settle;
+    }
+    let rates_file = args.rates.or_else(|| {
+        config::config_dir()
+            .map(|dir| dir.join("rates.toml"))
+            .filter(|path| path.exists() && !session::is_session(&file))
     });
-    
-    // 2. Setup terminal UI renderer
-    
-    // Noncanonical mode, which eliminates the need to wait
-    // for an Enter by the user to react to the input.
-    enable_raw_mode()?;
-    // setup terminal
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen/* , EnableMouseCapture */)?;
-    let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
-    terminal.clear()?;
+    match rates_file.map(ExchangeRates::load) {
+        Some(Ok(rates)) => data.rates = rates,
+        Some(Err(err)) => {
+            eprintln!("Error: {}", err);
+            return ExitCode::FAILURE;
+        }
+        None if session::is_session(&file) => {}
+        None => data.rates = ExchangeRates::new(&data.settlement_currency),
+    }
+    let mut receipt_files = vec![receipt_file(&file); data.receipts.len()];
+    // The other files are split along with the first, adding the rates
+    // sessions among them bring
+    for other in others {
+        match session::load_file(other, &currency) {
+            Ok(other_data) => {
+                data.merge(other_data);
+                receipt_files.resize(data.receipts.len(), receipt_file(other));
+            }
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    // Exporting from the command line skips the interface
+    if let Some(format) = args.export.or(args.output.as_ref().map(|_| config.export_format)) {
+        if let Err(err) = export::check_rates(&data, format) {
+            eprintln!("Error: {} (see --rates)", err);
+            return ExitCode::FAILURE;
+        }
+        let text = export::render(&data, &config.format, format);
+        match args.output {
+            Some(path) => {
+                if let Err(err) = std::fs::write(&path, text) {
+                    eprintln!("Error: {}: {}", path.display(), err);
+                    return ExitCode::FAILURE;
+                }
+            }
+            None => print!("{}", text),
+        }
+        return ExitCode::SUCCESS;
+    }
 
     // App state
     // let mut app = AppState::default();
-    let mut app = AppState::with_data(Data::load(&args[1])?);
-    
-    loop {
-        // Draw
-        terminal.draw(|f| {
-            ui(f, &app);
-        })?;
-
-        
-        // Input handlers
-        let input = rx.recv()?;
-        // Handle items input
-        let exit = match app.focused {
-            FocusedWindow::Items(_) => items_input_handler(&input, &mut app),
-            FocusedWindow::People(_) => people_input_handler(&input, &mut app),
-            FocusedWindow::OwnerSelector(_,_,_) => owner_selector_input_handler(&input, &mut app),
-            FocusedWindow::RestOwnerSelector(_) => rest_owner_selector_input_handler(&input, &mut app),
-            FocusedWindow::AddPerson(_) => add_person_input_handler(&input, &mut app),
-        };
-        // Exit
-        if exit {
-            disable_raw_mode()?;
-            terminal.show_cursor()?;
-            break;
+    let mut app = AppState::with_config(data, config);
+    app.session_path = Some(session::session_path(&file));
+    app.session_owned = session::is_session(&file);
+    app.receipt_files = receipt_files;
+    app.receipt_currency = currency;
+    app.autosave_path = autosave::path(&args.files);
+
+    // Offer back what a crash left unsaved
+    if let Some(recovered) = app.autosave_path.as_deref()
+        .and_then(|path| autosave::recover(path, &args.files, &app.data))
+    {
+        app.dialog = Some(Dialog {
+            title: "Recover",
+            message: format!(
+                "{} has changes that weren't saved when konta last closed. Recover them?",
+                file.display(),
+            ),
+            confirm: "Recover",
+            action: DialogAction::Recover(Box::new(recovered)),
+        });
+    }
+
+    // The terminal is only touched once the input is known to be good,
+    // and is restored before any error is printed
+    match run(&mut app) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            ExitCode::FAILURE
         }
     }
+}
+
+fn run(app: &mut AppState) -> io::Result<()> {
+    // 1. Setup terminal UI renderer
+
+    // Noncanonical mode, which eliminates the need to wait
+    // for an Enter by the user to react to the input.
+    let mut terminal = TerminalGuard::enter()?;
 
+    // 2. Setup input Receiver thread
+    let (tx, rx) = mpsc::channel();
+    let running = Arc::new(AtomicBool::new(true));
+    let input_thread = spawn_input_thread(tx.clone(), Arc::clone(&running));
 
-    // restore terminal
-    disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
-    terminal.show_cursor()?;
+    // One watcher per receipt file, including those imported later
+    let mut watch_threads = Vec::new();
+    let mut watched = 0;
+    let mut watch_new = |app: &AppState| {
+        while watched < app.receipt_files.len() {
+            if let Some(path) = &app.receipt_files[watched] {
+                let currency = app.data.receipts[watched].currency.clone();
+                watch_threads.push(spawn_watch_thread(watched, path.clone(), currency, tx.clone(), Arc::clone(&running)));
+            }
+            watched += 1;
+        }
+    };
 
-    Ok(())
-}
\ No newline at end of file
+    // Redraw only once something happened, the input thread being done
+    // means there's nothing more to wait for
+    let result = (|| {
+        watch_new(app);
+        terminal.draw(|f| ui(f, app))?;
+        while let Ok(message) = rx.recv() {
+            match message {
+                Message::Input(event) => {
+                    if handle_input(&event, app) {
+                        // Quitting leaves nothing to recover
+                        app.discard_autosave();
+                        break;
+                    }
+                }
+                Message::Closed => break,
+                Message::Reloaded(receipt_idx, Ok(data)) => app.reload(receipt_idx, data),
+                Message::Reloaded(_, Err(err)) => app.status = Some(format!("Reload failed: {}", err)),
+            }
+            watch_new(app);
+            if let Err(err) = app.autosave() {
+                app.status = Some(format!("Autosave failed: {}", err));
+            }
+            terminal.draw(|f| ui(f, app))?;
+        }
+        Ok(())
+    })();
+    running.store(false, Ordering::Relaxed);
+    // A panicking thread has nothing left to clean up
+    let _ = input_thread.join();
+    for watch_thread in watch_threads {
+        let _ = watch_thread.join();
+    }
+    result
+}
@@ -0,0 +1,596 @@
+use crate::currency::ExchangeRates;
+
+pub struct Data {
+    pub receipts: Vec<Receipt>,
+    pub items: Vec<Item>,
+    pub people: Vec<String>,
+    pub charges: Vec<Charge>,
+    // Currency people's totals are computed in
+    pub settlement_currency: String,
+    pub rates: ExchangeRates,
+}
+
+pub struct Receipt {
+    pub name: String,
+    // ISO code of the currency its amounts are in
+    pub currency: String,
+    // Total stated by the store, used to catch parser misses
+    pub expected_total: Option<f32>,
+    // Person who paid it, whom everyone else owes their share
+    pub payer: Option<usize>,
+}
+
+// How a receipt-level charge is divided between people
+pub enum SplitPolicy {
+    Equal,
+    // Proportional to each person's items subtotal
+    Proportional,
+    People(Vec<usize>),
+}
+
+// Receipt-level cost that isn't an item (delivery, service fee, tip),
+// or a credit when the amount is negative (voucher, card balance)
+pub struct Charge {
+    pub receipt: usize,
+    pub description: String,
+    pub amount: f32,
+    pub split: SplitPolicy,
+    // Added in konta rather than read from the receipt, so reloading the
+    // receipt leaves it
+    pub manual: bool,
+}
+
+impl Charge {
+    pub fn is_credit(&self) -> bool {
+        self.amount < 0.0
+    }
+}
+
+#[derive(Clone)]
+pub struct Owner {
+    pub person: usize,
+    pub percentage: f32,
+}
+
+#[derive(Clone)]
+pub struct Item {
+    pub receipt: usize,
+    pub description: String,
+    pub category: String,
+    pub quantity: f32,
+    // e.g. "Kg", empty when counted in units
+    pub unit: String,
+    // Price of the whole quantity
+    pub price: f32,
+    pub owners: Vec<Owner>,
+    // New or changed when the receipt was last reloaded, until given
+    // owners
+    pub flagged: bool,
+    // Added or duplicated in konta rather than read from the receipt, so
+    // reloading the receipt leaves it
+    pub manual: bool,
+}
+
+// A person's items grouped by category, along with their charges
+pub struct Breakdown {
+    pub categories: Vec<CategoryBreakdown>,
+    // (charge index, share fraction, amount)
+    pub charges: Vec<(usize, f32, f32)>,
+    pub total: f32,
+}
+
+pub struct CategoryBreakdown {
+    pub category: String,
+    // (item index, share fraction, amount)
+    pub items: Vec<(usize, f32, f32)>,
+    pub subtotal: f32,
+}
+
+impl Breakdown {
+    // Number of lines needed to display it, one per category, item and charge
+    // plus the charges header and total
+    pub fn lines(&self) -> usize {
+        let items: usize = self.categories.iter().map(|c| 1 + c.items.len()).sum();
+        let charges = if self.charges.is_empty() { 0 } else { 1 + self.charges.len() };
+        items + charges + 1
+    }
+}
+
+impl Data {
+    pub fn set_item_owner(&mut self, item_idx: usize, person_idx: usize) {
+        let owner = Owner {
+            person: person_idx,
+            percentage: 1.0,
+        };
+
+        self.items[item_idx].flagged = false;
+        let size = self.items[item_idx].owners.len();
+        // Special case to avoid reallocation
+        if size == 1 {
+            self.items[item_idx].owners[0] = owner;
+        } else {
+            self.items[item_idx].owners = vec![owner];
+        }
+    }
+
+    pub fn set_item_owners(&mut self, item_idx: usize, owners: Vec<Owner>) {
+        self.items[item_idx].flagged = false;
+        self.items[item_idx].owners = owners;
+    }
+
+    pub fn set_rest_items_owner(&mut self, person_idx: usize) {
+        for item in &mut self.items {
+            if item.owners.is_empty() {
+                item.owners.push(Owner {
+                    person: person_idx,
+                    percentage: 1.0,
+                });
+                item.flagged = false;
+            }
+        }
+    }
+
+    // Adds an item after the last one of its receipt, keeping each
+    // receipt's items together
+    pub fn add_item(&mut self, item: Item) -> usize {
+        let position = self.items.iter()
+            .rposition(|other| other.receipt == item.receipt)
+            .map_or(self.items.len(), |last| last + 1);
+        self.items.insert(position, item);
+        position
+    }
+
+    // Adds the receipts of another session after these, with their items
+    // and charges. People are matched by name, those not here being added
+    // if they own, pay or share anything, and rates for currencies not
+    // known here are taken in. Returns the index of the first receipt
+    // added.
+    pub fn merge(&mut self, other: Data) -> usize {
+        let first = self.receipts.len();
+        self.rates.merge(&other.rates);
+        let mut people: Vec<Option<usize>> = vec![None; other.people.len()];
+        let mut person = |person: usize| *people[person].get_or_insert_with(|| {
+            let name = &other.people[person];
+            self.people.iter().position(|existing| existing == name).unwrap_or_else(|| {
+                self.people.push(name.clone());
+                self.people.len() - 1
+            })
+        });
+        for mut receipt in other.receipts {
+            receipt.payer = receipt.payer.map(&mut person);
+            self.receipts.push(receipt);
+        }
+        for mut item in other.items {
+            item.receipt += first;
+            for owner in &mut item.owners {
+                owner.person = person(owner.person);
+            }
+            self.items.push(item);
+        }
+        for mut charge in other.charges {
+            charge.receipt += first;
+            if let SplitPolicy::People(sharing) = &mut charge.split {
+                for sharer in sharing.iter_mut() {
+                    *sharer = person(*sharer);
+                }
+            }
+            self.charges.push(charge);
+        }
+        first
+    }
+
+    // Copies an item, owners included, right below it
+    pub fn duplicate_item(&mut self, item_idx: usize) -> usize {
+        let mut copy = self.items[item_idx].clone();
+        copy.manual = true;
+        self.items.insert(item_idx + 1, copy);
+        item_idx + 1
+    }
+
+    // Moves `quantity` of an item to a new line below it with the same
+    // owners, the price split to the cent so the sum stays the same.
+    // Returns None unless some quantity is left on both lines.
+    pub fn split_item(&mut self, item_idx: usize, quantity: f32) -> Option<usize> {
+        let item = &mut self.items[item_idx];
+        if quantity <= 0.0 || quantity >= item.quantity {
+            return None;
+        }
+        let price = (item.price * quantity / item.quantity * 100.0).round() / 100.0;
+        let mut part = item.clone();
+        part.quantity = quantity;
+        part.price = price;
+        item.quantity -= quantity;
+        item.price -= price;
+        self.items.insert(item_idx + 1, part);
+        Some(item_idx + 1)
+    }
+
+    pub fn remove_item(&mut self, item_idx: usize) {
+        self.items.remove(item_idx);
+    }
+
+    pub fn add_charge(&mut self, charge: Charge) -> usize {
+        self.charges.push(charge);
+        self.charges.len() - 1
+    }
+
+    pub fn set_charge_split(&mut self, charge_idx: usize, split: SplitPolicy) {
+        self.charges[charge_idx].split = split;
+    }
+
+    pub fn remove_charge(&mut self, charge_idx: usize) {
+        self.charges.remove(charge_idx);
+    }
+
+    // Replaces what a receipt's file gave with a new parse of it, leaving
+    // the items and charges added by hand. Items the same as before keep
+    // their owners, as do lines split off them as long as they still add
+    // up, and so do those with the same description but another quantity
+    // or price, which are flagged along with the new ones. Charges keep
+    // their split by description, and a total entered by hand stays
+    // unless the file now states one. Returns how many items were
+    // flagged and how many went away.
+    pub fn reload_receipt(&mut self, receipt_idx: usize, fresh: Data) -> (usize, usize) {
+        let position = self.items.iter()
+            .position(|item| item.receipt == receipt_idx)
+            .unwrap_or(self.items.len());
+        let (receipt_items, others): (Vec<Item>, Vec<Item>) = std::mem::take(&mut self.items)
+            .into_iter()
+            .partition(|item| item.receipt == receipt_idx);
+        let (manual, mut old): (Vec<Item>, Vec<Item>) = receipt_items.into_iter().partition(|item| item.manual);
+
+        // Each new item along with the old lines it replaces, if any
+        let mut items: Vec<(Item, Vec<Item>)> = Vec::with_capacity(fresh.items.len());
+        for mut item in fresh.items {
+            item.receipt = receipt_idx;
+            let same = old.iter().position(|previous| {
+                previous.description == item.description
+                    && previous.quantity == item.quantity
+                    && previous.price == item.price
+            });
+            let previous = same.map(|previous| vec![old.remove(previous)]).unwrap_or_default();
+            items.push((item, previous));
+        }
+        let mut flagged = 0;
+        for (item, previous) in items.iter_mut().filter(|(_, previous)| previous.is_empty()) {
+            let (parts, rest): (Vec<Item>, Vec<Item>) = std::mem::take(&mut old)
+                .into_iter()
+                .partition(|part| part.description == item.description);
+            old = rest;
+            let quantity: f32 = parts.iter().map(|part| part.quantity).sum();
+            let price: f32 = parts.iter().map(|part| part.price).sum();
+            if parts.len() > 1 && (quantity - item.quantity).abs() < 0.001 && (price - item.price).abs() < 0.005 {
+                *previous = parts;
+                continue;
+            }
+            let mut parts = parts.into_iter();
+            if let Some(part) = parts.next() {
+                item.owners = part.owners;
+            }
+            // Lines split off a changed item are left for the user to
+            // split again
+            old.extend(parts);
+            item.flagged = true;
+            flagged += 1;
+        }
+        let removed = old.len();
+
+        let mut receipt_items = Vec::with_capacity(items.len() + manual.len());
+        for (item, previous) in items {
+            if previous.is_empty() {
+                receipt_items.push(item);
+            }
+            else {
+                receipt_items.extend(previous);
+            }
+        }
+        receipt_items.extend(manual);
+        let position = position.min(others.len());
+        self.items = others;
+        self.items.splice(position..position, receipt_items);
+
+        let (mut old_charges, other_charges): (Vec<Charge>, Vec<Charge>) = std::mem::take(&mut self.charges)
+            .into_iter()
+            .partition(|charge| charge.receipt == receipt_idx && !charge.manual);
+        self.charges = other_charges;
+        for mut charge in fresh.charges {
+            charge.receipt = receipt_idx;
+            if let Some(previous) = old_charges.iter().position(|previous| previous.description == charge.description) {
+                charge.split = old_charges.remove(previous).split;
+            }
+            self.charges.push(charge);
+        }
+        if let Some(total) = fresh.receipts[0].expected_total {
+            self.receipts[receipt_idx].expected_total = Some(total);
+        }
+        (flagged, removed)
+    }
+
+    // Removes a person, handing their share of each item to the remaining
+    // owners. Items only they owned become unowned.
+    pub fn remove_person(&mut self, person_idx: usize) {
+        self.people.remove(person_idx);
+        for item in &mut self.items {
+            item.owners.retain(|owner| owner.person != person_idx);
+            let remaining: f32 = item.owners.iter().map(|owner| owner.percentage).sum();
+            for owner in &mut item.owners {
+                owner.percentage /= remaining;
+                if owner.person > person_idx {
+                    owner.person -= 1;
+                }
+            }
+        }
+        for charge in &mut self.charges {
+            if let SplitPolicy::People(people) = &mut charge.split {
+                people.retain(|person| *person != person_idx);
+                for person in people.iter_mut() {
+                    if *person > person_idx {
+                        *person -= 1;
+                    }
+                }
+                if people.is_empty() {
+                    charge.split = SplitPolicy::Equal;
+                }
+            }
+        }
+        for receipt in &mut self.receipts {
+            receipt.payer = match receipt.payer {
+                Some(payer) if payer == person_idx => None,
+                Some(payer) if payer > person_idx => Some(payer - 1),
+                payer => payer,
+            };
+        }
+    }
+
+    // Amount from a receipt in the settlement currency, unconverted
+    // when the rate is unknown
+    pub fn to_settlement(&self, amount: f32, receipt_idx: usize) -> f32 {
+        let currency = &self.receipts[receipt_idx].currency;
+        self.rates.convert(amount, currency, &self.settlement_currency).unwrap_or(amount)
+    }
+
+    // Receipt currencies without a rate to the settlement currency
+    pub fn missing_rates(&self) -> Vec<&str> {
+        let mut missing: Vec<&str> = Vec::new();
+        for receipt in &self.receipts {
+            let currency = receipt.currency.as_str();
+            if self.rates.convert(1.0, currency, &self.settlement_currency).is_none()
+                && !missing.contains(&currency)
+            {
+                missing.push(currency);
+            }
+        }
+        missing
+    }
+
+    // Each person's share of the items they own, in the settlement currency
+    pub fn compute_subtotals(&self) -> Vec<f32> {
+        let mut totals = vec![0f32; self.people.len()];
+        for item in &self.items {
+            let price = self.to_settlement(item.price, item.receipt);
+            for owner in &item.owners {
+                totals[owner.person] += owner.percentage * price;
+            }
+        }
+        totals
+    }
+
+    // Like compute_subtotals, for each receipt on its own
+    pub fn receipt_subtotals(&self) -> Vec<Vec<f32>> {
+        let mut totals = vec![vec![0f32; self.people.len()]; self.receipts.len()];
+        for item in &self.items {
+            let price = self.to_settlement(item.price, item.receipt);
+            for owner in &item.owners {
+                totals[item.receipt][owner.person] += owner.percentage * price;
+            }
+        }
+        totals
+    }
+
+    // Each person's share of a charge in the settlement currency, given
+    // their items subtotals on the charge's receipt
+    pub fn charge_shares(&self, charge: &Charge, subtotals: &[f32]) -> Vec<f32> {
+        let mut shares = vec![0f32; self.people.len()];
+        if shares.is_empty() {
+            return shares;
+        }
+        let amount = self.to_settlement(charge.amount, charge.receipt);
+        let assigned: f32 = subtotals.iter().sum();
+        match &charge.split {
+            // Nothing assigned yet falls back to an equal split
+            SplitPolicy::Proportional if assigned > 0.0 => {
+                for (share, subtotal) in shares.iter_mut().zip(subtotals) {
+                    *share = amount * subtotal / assigned;
+                }
+            }
+            SplitPolicy::People(people) if !people.is_empty() => {
+                let share = amount / (people.len() as f32);
+                for person in people {
+                    shares[*person] += share;
+                }
+            }
+            _ => {
+                let share = amount / (shares.len() as f32);
+                shares.iter_mut().for_each(|s| *s = share);
+            }
+        }
+        shares
+    }
+
+    // Items a person takes part in, as (item index, share fraction)
+    pub fn person_shares(&self, person_idx: usize) -> Vec<(usize, f32)> {
+        let mut shares = Vec::new();
+        for (i, item) in self.items.iter().enumerate() {
+            let fraction: f32 = item.owners.iter()
+                .filter(|owner| owner.person == person_idx)
+                .map(|owner| owner.percentage)
+                .sum();
+            if fraction > 0.0 {
+                shares.push((i, fraction));
+            }
+        }
+        shares
+    }
+
+    pub fn person_breakdown(&self, person_idx: usize) -> Breakdown {
+        let mut categories: Vec<CategoryBreakdown> = Vec::new();
+        for (item_idx, fraction) in self.person_shares(person_idx) {
+            let item = &self.items[item_idx];
+            let amount = fraction * self.to_settlement(item.price, item.receipt);
+            // Keep categories in the order they first appear in the receipt
+            let category = match categories.iter().position(|c| c.category == item.category) {
+                Some(pos) => &mut categories[pos],
+                None => {
+                    categories.push(CategoryBreakdown {
+                        category: item.category.clone(),
+                        items: Vec::new(),
+                        subtotal: 0.0,
+                    });
+                    categories.last_mut().unwrap()
+                }
+            };
+            category.items.push((item_idx, fraction, amount));
+            category.subtotal += amount;
+        }
+
+        let subtotals = self.receipt_subtotals();
+        let mut charges = Vec::new();
+        for (charge_idx, charge) in self.charges.iter().enumerate() {
+            let amount = self.charge_shares(charge, &subtotals[charge.receipt])[person_idx];
+            if amount != 0.0 {
+                let fraction = amount / self.to_settlement(charge.amount, charge.receipt);
+                charges.push((charge_idx, fraction, amount));
+            }
+        }
+
+        let total = categories.iter().fold(0.0, |total, c| total + c.subtotal)
+            + charges.iter().fold(0.0, |total, (_, _, amount)| total + amount);
+        Breakdown { categories, charges, total }
+    }
+
+    pub fn compute_total(&self) -> Vec<f32> {
+        let subtotals = self.receipt_subtotals();
+        let mut totals = self.compute_subtotals();
+        for charge in &self.charges {
+            let shares = self.charge_shares(charge, &subtotals[charge.receipt]);
+            for (total, share) in totals.iter_mut().zip(shares) {
+                *total += share;
+            }
+        }
+        totals
+    }
+
+    // Each person's share of one receipt, in the settlement currency
+    pub fn receipt_shares(&self, receipt_idx: usize) -> Vec<f32> {
+        let subtotals = self.receipt_subtotals().swap_remove(receipt_idx);
+        let mut shares = subtotals.clone();
+        for charge in self.charges.iter().filter(|charge| charge.receipt == receipt_idx) {
+            for (share, charge_share) in shares.iter_mut().zip(self.charge_shares(charge, &subtotals)) {
+                *share += charge_share;
+            }
+        }
+        shares
+    }
+
+    // Transfers that settle every receipt with a payer, as (from, to,
+    // amount), with as few transfers as the greedy approach finds
+    pub fn settlements(&self) -> Vec<(usize, usize, f32)> {
+        // What each person is owed (positive) or owes (negative)
+        let mut balances = vec![0f32; self.people.len()];
+        for (receipt_idx, receipt) in self.receipts.iter().enumerate() {
+            if let Some(payer) = receipt.payer {
+                for (person, share) in self.receipt_shares(receipt_idx).into_iter().enumerate() {
+                    balances[person] -= share;
+                    balances[payer] += share;
+                }
+            }
+        }
+
+        let mut transfers = Vec::new();
+        loop {
+            let debtor = (0..balances.len()).min_by(|a, b| balances[*a].total_cmp(&balances[*b]));
+            let creditor = (0..balances.len()).max_by(|a, b| balances[*a].total_cmp(&balances[*b]));
+            let (debtor, creditor) = match (debtor, creditor) {
+                (Some(debtor), Some(creditor)) => (debtor, creditor),
+                _ => break,
+            };
+            let amount = balances[creditor].min(-balances[debtor]);
+            // Leftover cents are rounding noise
+            if amount < 0.005 {
+                break;
+            }
+            balances[debtor] += amount;
+            balances[creditor] -= amount;
+            transfers.push((debtor, creditor, amount));
+        }
+        transfers
+    }
+
+    // Price of the items nobody owns yet, in the settlement currency
+    pub fn unassigned_total(&self) -> f32 {
+        self.items.iter()
+            .filter(|item| item.owners.is_empty())
+            .fold(0.0, |total, item| total + self.to_settlement(item.price, item.receipt))
+    }
+
+    // Sum of every parsed item price of a receipt, in its currency
+    pub fn items_total(&self, receipt_idx: usize) -> f32 {
+        self.items.iter()
+            .filter(|item| item.receipt == receipt_idx)
+            .fold(0.0, |total, item| total + item.price)
+    }
+
+    pub fn charges_total(&self, receipt_idx: usize) -> f32 {
+        self.charges.iter()
+            .filter(|charge| charge.receipt == receipt_idx && !charge.is_credit())
+            .fold(0.0, |total, charge| total + charge.amount)
+    }
+
+    // Sum of vouchers and loyalty credits (negative)
+    pub fn credits_total(&self, receipt_idx: usize) -> f32 {
+        self.charges.iter()
+            .filter(|charge| charge.receipt == receipt_idx && charge.is_credit())
+            .fold(0.0, |total, charge| total + charge.amount)
+    }
+
+    // What a receipt comes to, in its currency
+    pub fn receipt_total(&self, receipt_idx: usize) -> f32 {
+        self.items_total(receipt_idx) + self.charges_total(receipt_idx) + self.credits_total(receipt_idx)
+    }
+
+    // Difference between a receipt's stated total and its parsed items,
+    // charges and credits, `None` if the stated total is unknown
+    pub fn reconciliation(&self, receipt_idx: usize) -> Option<f32> {
+        self.receipts[receipt_idx].expected_total.map(|total| {
+            total - self.items_total(receipt_idx)
+                - self.charges_total(receipt_idx)
+                - self.credits_total(receipt_idx)
+        })
+    }
+}
+
+pub fn from_indices_to_owners(people: &[usize]) -> Vec<Owner> {
+    let mut owners = Vec::with_capacity(people.len());
+    let percentage = 1f32 / (people.len() as f32);
+    for person in people {
+        owners.push(Owner {
+            person: *person,
+            percentage,
+        });
+    }
+    owners
+}
+
+// [{"jojo",0.2}, {"jojo",0.2}, {"jojo",0.2}, {"jojo",0.2}, {"bu",0.2}]
+// turns into
+// [{"jojo",0.8}, {"bu",0.2}]
+pub fn flatten_owners(owners: &mut Vec<Owner>) {
+    owners.dedup_by(|a, b| {
+        if a.person == b.person {
+            b.percentage += a.percentage;
+            true
+        } else {
+            false
+        }
+    });
+}
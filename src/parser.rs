@@ -0,0 +1,208 @@
+use regex::Regex;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::currency::ExchangeRates;
+use crate::model::*;
+
+// Receipt-level lines, compiled once and shared by every parse
+struct Patterns {
+    // Not indented and ending with an amount, e.g. "Total: 45,67 €"
+    summary: Regex,
+    total: Regex,
+    charge: Regex,
+    credit: Regex,
+}
+
+fn patterns() -> &'static Patterns {
+    static PATTERNS: OnceLock<Patterns> = OnceLock::new();
+    PATTERNS.get_or_init(|| Patterns {
+        summary: Regex::new(r"^(\S.*?):?\s+(-?\d+,\d+)\s*€?\s*$").unwrap(),
+        total: Regex::new(r"(?i)^(total|a pagar)").unwrap(),
+        charge: Regex::new(
+            r"(?i)(entrega|delivery|portes|servi[çc]o|service|taxa|fee|gorjeta|tip)"
+        ).unwrap(),
+        credit: Regex::new(
+            r"(?i)(vale|voucher|cup[ãa]o|coupon|desconto|saldo|cart[ãa]o|cr[ée]dito|credit)"
+        ).unwrap(),
+    })
+}
+
+// What the next indented line of an item holds
+enum State {
+    Description,
+    Quantity,
+    Discount,
+    Price,
+}
+
+// Reads a receipt, taking its amounts to be in `currency`
+pub fn load<P: AsRef<Path>>(filename: P, currency: &str) -> io::Result<Data> {
+    let name = filename.as_ref()
+        .file_stem()
+        .map_or_else(|| "receipt".into(), |stem| stem.to_string_lossy().into_owned());
+    let file = File::open(filename)?;
+    parse(BufReader::new(file), &name, currency)
+}
+
+// Reads a receipt line by line. Items are four lines indented by four
+// spaces (description, quantity, discount and price) under category
+// headers, e.g.
+//   Frigorífico
+//       Queijo Flamengo
+//       1
+//       0,00 €
+//       2,35 €
+pub fn parse<R: BufRead>(mut reader: R, name: &str, currency: &str) -> io::Result<Data> {
+    let patterns = patterns();
+    let mut items = Vec::<Item>::with_capacity(20);
+    let mut charges = Vec::<Charge>::new();
+    let mut expected_total = None;
+
+    let mut state = State::Description;
+    let mut category = String::new();
+    let mut item = empty_item();
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 0 {
+        let text = line.trim_end_matches(['\n', '\r']);
+        let indented = text.strip_prefix("    ");
+        match state {
+            State::Description => match indented {
+                Some(description) if !description.trim().is_empty() => {
+                    item.description = description.trim().to_string();
+                    item.category = category.clone();
+                    state = State::Quantity;
+                }
+                Some(_) => {}
+                None => {
+                    if let Some(capture) = patterns.summary.captures(text) {
+                        let label = capture.get(1).unwrap().as_str();
+                        let amount = parse_price(capture.get(2).unwrap().as_str());
+                        if patterns.total.is_match(label) {
+                            expected_total = amount;
+                        }
+                        // Credits are stored negative whichever way the receipt writes them
+                        else if let Some(amount) = amount.filter(|a| *a < 0.0 || patterns.credit.is_match(label)) {
+                            charges.push(Charge {
+                                receipt: 0,
+                                description: label.to_string(),
+                                amount: -amount.abs(),
+                                split: SplitPolicy::Proportional,
+                                manual: false,
+                            });
+                        }
+                        else if let Some(amount) = amount.filter(|_| patterns.charge.is_match(label)) {
+                            charges.push(Charge {
+                                receipt: 0,
+                                description: label.to_string(),
+                                amount,
+                                split: SplitPolicy::Equal,
+                                manual: false,
+                            });
+                        }
+                    }
+                    // Any other non-indented line is a category header
+                    else if !text.trim().is_empty() {
+                        category = text.trim().to_string();
+                    }
+                }
+            },
+            State::Quantity => {
+                // Counted items or weights, e.g. "2" or "1.83 Kg"
+                match indented.and_then(parse_quantity) {
+                    Some((quantity, unit)) => {
+                        item.quantity = quantity;
+                        item.unit = unit.to_string();
+                        state = State::Discount;
+                    }
+                    None => state = State::Description,
+                }
+            }
+            State::Discount => {
+                if indented.and_then(|text| leading_amount(text, true)).is_some() {
+                    state = State::Price;
+                }
+            }
+            State::Price => {
+                if let Some(price) = indented.and_then(|text| leading_amount(text, false)) {
+                    item.price = price;
+                    if price != 0.0 {
+                        items.push(std::mem::replace(&mut item, empty_item()));
+                    }
+                }
+                state = State::Description;
+            }
+        }
+        line.clear();
+    }
+    Ok(Data {
+        receipts: vec![Receipt {
+            name: name.to_string(),
+            currency: currency.to_string(),
+            expected_total,
+            payer: None,
+        }],
+        items,
+        people: vec!["jojo".into()],
+        charges,
+        settlement_currency: currency.to_string(),
+        rates: ExchangeRates::new(currency),
+    })
+}
+
+fn empty_item() -> Item {
+    Item {
+        receipt: 0,
+        description: String::new(),
+        category: String::new(),
+        quantity: 0.0,
+        unit: String::new(),
+        price: 0.0,
+        owners: Vec::new(),
+        flagged: false,
+        manual: false,
+    }
+}
+
+// "2" or "1.83 Kg", the unit being the word right after the number
+fn parse_quantity(text: &str) -> Option<(f32, &str)> {
+    let end = number_end(text, true);
+    let quantity = text[..end].replace(',', ".").parse().ok()?;
+    let unit = text[end..].split_whitespace().next().unwrap_or("");
+    Some((quantity, unit))
+}
+
+// Amount at the start of the text written as "2,58", anything after it
+// being ignored
+fn leading_amount(text: &str, signed: bool) -> Option<f32> {
+    let unsigned = if signed { text.strip_prefix('-').unwrap_or(text) } else { text };
+    let (units, rest) = unsigned.split_once(',')?;
+    let cents_len = number_end(rest, false);
+    if units.is_empty() || cents_len == 0 || !units.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let amount = units.parse::<f32>().ok()? + rest[..cents_len].parse::<f32>().ok()? * 0.01;
+    Some(if unsigned.len() < text.len() { -amount } else { amount })
+}
+
+// Length of the leading digits, with one decimal part if allowed
+fn number_end(text: &str, decimals: bool) -> usize {
+    let bytes = text.as_bytes();
+    let mut end = bytes.iter().take_while(|b| b.is_ascii_digit()).count();
+    if decimals && end > 0 && matches!(bytes.get(end), Some(b'.' | b',')) {
+        let fraction = bytes[end + 1..].iter().take_while(|b| b.is_ascii_digit()).count();
+        if fraction > 0 {
+            end += 1 + fraction;
+        }
+    }
+    end
+}
+
+// Parses an amount written either as "2,58" or "2.58", optionally
+// followed by the currency symbol
+pub fn parse_price(text: &str) -> Option<f32> {
+    let text = text.trim().trim_end_matches('€').trim();
+    text.replace(',', ".").parse::<f32>().ok()
+}
@@ -0,0 +1,319 @@
+// Whole sessions as JSON, for saving work and for other tools to read
+// results or pre-assign items. The schema is described in README.md and
+// only changes in backwards compatible ways unless `version` is bumped.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::currency::{parse_code, ExchangeRates, RatesTable};
+use crate::model::*;
+use crate::parser;
+
+pub const VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SessionFile {
+    version: u32,
+    #[serde(default)]
+    settlement_currency: Option<String>,
+    #[serde(default)]
+    rates: Option<RatesTable>,
+    #[serde(default)]
+    receipts: Vec<ReceiptEntry>,
+    people: Vec<PersonEntry>,
+    items: Vec<ItemEntry>,
+    #[serde(default)]
+    charges: Vec<ChargeEntry>,
+    // Computed on export, ignored on import
+    #[serde(default)]
+    totals: Option<Totals>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ReceiptEntry {
+    name: String,
+    currency: String,
+    #[serde(default)]
+    expected_total: Option<f32>,
+    // Person's name
+    #[serde(default)]
+    payer: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PersonEntry {
+    name: String,
+    // What they pay in the settlement currency, ignored on import
+    #[serde(default)]
+    total: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ItemEntry {
+    // Index into `receipts`
+    #[serde(default)]
+    receipt: usize,
+    description: String,
+    #[serde(default)]
+    category: String,
+    #[serde(default = "whole")]
+    quantity: f32,
+    #[serde(default)]
+    unit: String,
+    price: f32,
+    #[serde(default)]
+    owners: Vec<OwnerEntry>,
+    // Added by hand rather than read from the receipt
+    #[serde(default)]
+    manual: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OwnerEntry {
+    person: String,
+    // Fraction of the item, shares of an item should add up to 1
+    #[serde(default = "whole")]
+    share: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ChargeEntry {
+    #[serde(default)]
+    receipt: usize,
+    description: String,
+    // Negative for credits
+    amount: f32,
+    #[serde(default)]
+    split: SplitEntry,
+    // Added by hand rather than read from the receipt
+    #[serde(default)]
+    manual: bool,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+enum SplitEntry {
+    #[default]
+    Equal,
+    Proportional,
+    // Names of the people sharing it
+    People(Vec<String>),
+}
+
+#[derive(Serialize, Deserialize)]
+struct Totals {
+    items: f32,
+    charges: f32,
+    credits: f32,
+    unassigned: f32,
+}
+
+fn whole() -> f32 {
+    1.0
+}
+
+// Where the session of a receipt is saved, "receipt.txt" being saved as
+// "receipt.konta.json" and sessions in place
+pub fn session_path(input: &Path) -> PathBuf {
+    if is_session(input) {
+        input.to_path_buf()
+    }
+    else {
+        input.with_extension("konta.json")
+    }
+}
+
+pub fn is_session(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == "json")
+}
+
+pub fn to_json(data: &Data) -> String {
+    let totals = data.compute_total();
+    let settle = |amount: f32, receipt: usize| data.to_settlement(amount, receipt);
+    let file = SessionFile {
+        version: VERSION,
+        settlement_currency: Some(data.settlement_currency.clone()),
+        rates: Some(data.rates.to_table()),
+        receipts: data.receipts.iter()
+            .map(|receipt| ReceiptEntry {
+                name: receipt.name.clone(),
+                currency: receipt.currency.clone(),
+                expected_total: receipt.expected_total,
+                payer: receipt.payer.map(|payer| data.people[payer].clone()),
+            })
+            .collect(),
+        people: data.people.iter().zip(&totals)
+            .map(|(name, total)| PersonEntry { name: name.clone(), total: *total })
+            .collect(),
+        items: data.items.iter()
+            .map(|item| ItemEntry {
+                receipt: item.receipt,
+                description: item.description.clone(),
+                category: item.category.clone(),
+                quantity: item.quantity,
+                unit: item.unit.clone(),
+                price: item.price,
+                owners: item.owners.iter()
+                    .map(|owner| OwnerEntry {
+                        person: data.people[owner.person].clone(),
+                        share: owner.percentage,
+                    })
+                    .collect(),
+                manual: item.manual,
+            })
+            .collect(),
+        charges: data.charges.iter()
+            .map(|charge| ChargeEntry {
+                receipt: charge.receipt,
+                description: charge.description.clone(),
+                amount: charge.amount,
+                manual: charge.manual,
+                split: match &charge.split {
+                    SplitPolicy::Equal => SplitEntry::Equal,
+                    SplitPolicy::Proportional => SplitEntry::Proportional,
+                    SplitPolicy::People(people) => SplitEntry::People(
+                        people.iter().map(|person| data.people[*person].clone()).collect()
+                    ),
+                },
+            })
+            .collect(),
+        totals: Some(Totals {
+            items: data.items.iter()
+                .fold(0.0, |total, item| total + settle(item.price, item.receipt)),
+            charges: data.charges.iter()
+                .filter(|charge| !charge.is_credit())
+                .fold(0.0, |total, charge| total + settle(charge.amount, charge.receipt)),
+            credits: data.charges.iter()
+                .filter(|charge| charge.is_credit())
+                .fold(0.0, |total, charge| total + settle(charge.amount, charge.receipt)),
+            unassigned: data.unassigned_total(),
+        }),
+    };
+    // Every field serializes, so this can't fail
+    serde_json::to_string_pretty(&file).unwrap() + "\n"
+}
+
+// Reads a session, `name` naming its receipt when it has none
+pub fn from_json(text: &str, name: &str, default_currency: &str) -> Result<Data, String> {
+    let file: SessionFile = serde_json::from_str(text).map_err(|err| err.to_string())?;
+    if file.version > VERSION {
+        return Err(format!("unsupported session version {}", file.version));
+    }
+
+    let people: Vec<String> = file.people.into_iter().map(|person| person.name).collect();
+    for (i, name) in people.iter().enumerate() {
+        if people[..i].contains(name) {
+            return Err(format!("person '{}' appears twice", name));
+        }
+    }
+    let person_index = |name: &str| {
+        people.iter()
+            .position(|person| person == name)
+            .ok_or_else(|| format!("unknown person '{}'", name))
+    };
+    let currency_code = |text: &str| {
+        parse_code(text).ok_or_else(|| format!("invalid currency '{}'", text))
+    };
+
+    let mut receipts = Vec::with_capacity(file.receipts.len().max(1));
+    for receipt in file.receipts {
+        receipts.push(Receipt {
+            name: receipt.name,
+            currency: currency_code(&receipt.currency)?,
+            expected_total: receipt.expected_total,
+            payer: receipt.payer.as_deref().map(person_index).transpose()?,
+        });
+    }
+    if receipts.is_empty() {
+        receipts.push(Receipt {
+            name: name.to_string(),
+            currency: default_currency.to_string(),
+            expected_total: None,
+            payer: None,
+        });
+    }
+    let receipt_index = |receipt: usize| {
+        if receipt < receipts.len() {
+            Ok(receipt)
+        }
+        else {
+            Err(format!("unknown receipt {}", receipt))
+        }
+    };
+
+    let mut items = Vec::with_capacity(file.items.len());
+    for item in file.items {
+        let mut owners = Vec::with_capacity(item.owners.len());
+        for owner in item.owners {
+            if owner.share <= 0.0 || owner.share > 1.0 {
+                return Err(format!("invalid share {} of '{}'", owner.share, item.description));
+            }
+            owners.push(Owner { person: person_index(&owner.person)?, percentage: owner.share });
+        }
+        items.push(Item {
+            receipt: receipt_index(item.receipt)?,
+            description: item.description,
+            category: item.category,
+            quantity: item.quantity,
+            unit: item.unit,
+            price: item.price,
+            owners,
+            flagged: false,
+            manual: item.manual,
+        });
+    }
+
+    let mut charges = Vec::with_capacity(file.charges.len());
+    for charge in file.charges {
+        charges.push(Charge {
+            receipt: receipt_index(charge.receipt)?,
+            description: charge.description,
+            amount: charge.amount,
+            split: match charge.split {
+                SplitEntry::Equal => SplitPolicy::Equal,
+                SplitEntry::Proportional => SplitPolicy::Proportional,
+                SplitEntry::People(names) => SplitPolicy::People(
+                    names.iter().map(|name| person_index(name)).collect::<Result<_, _>>()?
+                ),
+            },
+            manual: charge.manual,
+        });
+    }
+
+    let settlement_currency = match file.settlement_currency {
+        Some(code) => currency_code(&code)?,
+        None => receipts[0].currency.clone(),
+    };
+    let rates = match file.rates {
+        Some(table) => ExchangeRates::from_table(table)?,
+        None => ExchangeRates::new(&settlement_currency),
+    };
+
+    Ok(Data { receipts, items, people, charges, settlement_currency, rates })
+}
+
+pub fn load(path: &Path, default_currency: &str) -> Result<Data, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|err| format!("{}: {}", path.display(), err))?;
+    let name = path.file_stem().map_or_else(|| "session".into(), |stem| stem.to_string_lossy());
+    from_json(&text, &name, default_currency)
+        .map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+// Reads a saved session, or a receipt taking its amounts to be in
+// `currency`
+pub fn load_file(path: &Path, currency: &str) -> Result<Data, String> {
+    if is_session(path) {
+        load(path, currency)
+    }
+    else {
+        parser::load(path, currency).map_err(|err| format!("{}: {}", path.display(), err))
+    }
+}
@@ -1,55 +1,228 @@
-use std::io;
-use std::path::Path;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::autosave;
+use crate::config::Config;
+use crate::currency::ExchangeRates;
+use crate::model::*;
+use crate::session;
+use crate::text_input::TextInput;
 
 pub struct AppState {
     pub focused: FocusedWindow,
     pub data: Data,
+    pub config: Config,
+    pub view: View,
+    pub show_help: bool,
+    // Result of the last action, shown until the next key
+    pub status: Option<String>,
+    // Where the session is saved, and what was last saved or loaded
+    pub session_path: Option<PathBuf>,
+    pub saved: String,
+    // Whether the session file was loaded or saved in this run, a file
+    // already there otherwise being only overwritten once confirmed
+    pub session_owned: bool,
+    // Copy of the session kept in case of a crash, and what was last
+    // written to it
+    pub autosave_path: Option<PathBuf>,
+    pub autosaved: String,
+    // File each receipt was read from, if it's to be watched for changes
+    pub receipt_files: Vec<Option<PathBuf>>,
+    // Currency of imported receipts, from --currency or the config
+    pub receipt_currency: String,
+    // Receipt of the item or charge last selected, which the people
+    // panel acts on
+    pub last_receipt: usize,
+    // Panel and row selected before a prompt opened, returned to once
+    // it's closed
+    pub last_panel: FocusedWindow,
+    // Question shown over the focused window until answered
+    pub dialog: Option<Dialog>,
+}
+
+// Layout preferences toggled from the items panel
+#[derive(Default)]
+pub struct View {
+    pub sidebar_collapsed: bool,
+    pub wrap_descriptions: bool,
+    // Characters of each description scrolled out of view
+    pub description_offset: usize,
+}
+
+// Yes or no question asked before quitting with unsaved changes and
+// before changes that are hard to undo
+pub struct Dialog {
+    pub title: &'static str,
+    pub message: String,
+    // Label of the confirming choice, e.g. "Quit"
+    pub confirm: &'static str,
+    pub action: DialogAction,
+}
+
+// What a dialog does once confirmed
+pub enum DialogAction {
+    Quit,
+    AssignRest(usize),
+    RemovePerson(usize),
+    RemoveItem(usize),
+    RemoveCharge(usize),
+    // Save over a session file konta didn't write
+    Overwrite,
+    // Session left behind by a crash, dropped if not wanted
+    Recover(Box<Data>),
+}
+
+// File to watch for changes to receipts read from `path`, sessions
+// being only ever written by konta itself
+pub fn receipt_file(path: &Path) -> Option<PathBuf> {
+    Some(path.to_path_buf()).filter(|path| !session::is_session(path))
 }
 
 pub enum FocusedWindow {
     Items(usize),
     People(usize),
+    Charges(usize),
     OwnerSelector(usize, usize, Vec<usize>),
     RestOwnerSelector(usize),
-    AddPerson(String),
+    AddPerson(TextInput),
+    // Prompts for a receipt, by index
+    SetTotal(usize, TextInput),
+    AddCharge(usize, TextInput),
+    AddCredit(usize, TextInput),
+    SetRate(usize, TextInput),
+    // New item for a receipt, and how much of an item to split off
+    AddItem(usize, TextInput),
+    SplitItem(usize, TextInput),
+    // Path of another receipt or session to add
+    ImportReceipt(TextInput),
+    // Item being edited, the field and its text
+    EditItem(usize, ItemField, TextInput),
+    ChargeSplitSelector(usize, usize, Vec<usize>),
+    PersonDetail(usize, usize),
 }
 
-pub struct Data {
-    pub items: Vec<Item>,
-    pub people: Vec<String>,
+// Fields of an item that can be edited, in the order Tab goes through them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemField {
+    Description,
+    Quantity,
+    Unit,
+    Price,
+    Category,
+}
+
+impl ItemField {
+    pub const ALL: [ItemField; 5] = [
+        ItemField::Description,
+        ItemField::Quantity,
+        ItemField::Unit,
+        ItemField::Price,
+        ItemField::Category,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ItemField::Description => "Description",
+            ItemField::Quantity => "Quantity",
+            ItemField::Unit => "Unit",
+            ItemField::Price => "Price",
+            ItemField::Category => "Category",
+        }
+    }
+
+    // The field after (or before) this one, wrapping around
+    pub fn cycle(&self, forward: bool) -> ItemField {
+        let i = ItemField::ALL.iter().position(|field| field == self).unwrap();
+        let len = ItemField::ALL.len();
+        ItemField::ALL[if forward { (i + 1) % len } else { (i + len - 1) % len }]
+    }
 }
 
-pub struct Owner {
-    pub person: usize,
-    pub percentage: f32,
+pub fn split_to_string(split: &SplitPolicy, app: &AppState) -> String {
+    match split {
+        SplitPolicy::Equal => "equal".into(),
+        SplitPolicy::Proportional => "by subtotal".into(),
+        SplitPolicy::People(people) => people.iter()
+            .map(|person| app.data.people[*person].as_str())
+            .collect::<Vec<_>>()
+            .join(", "),
+    }
 }
 
-pub struct Item {
-    pub description: String,
-    pub quantity: u32,
-    pub price: f32,
-    pub owners: Vec<Owner>,
+impl FocusedWindow {
+    // Prompts where printable keys are typed rather than bound
+    pub fn is_text_input(&self) -> bool {
+        self.text_input().is_some()
+    }
+
+    pub fn text_input(&self) -> Option<&TextInput> {
+        match self {
+            FocusedWindow::AddPerson(input)
+            | FocusedWindow::SetTotal(_, input)
+            | FocusedWindow::AddCharge(_, input)
+            | FocusedWindow::AddCredit(_, input)
+            | FocusedWindow::SetRate(_, input)
+            | FocusedWindow::AddItem(_, input)
+            | FocusedWindow::SplitItem(_, input)
+            | FocusedWindow::ImportReceipt(input)
+            | FocusedWindow::EditItem(_, _, input) => Some(input),
+            _ => None,
+        }
+    }
 }
 
 impl Default for AppState {
     fn default() -> Self {
         let item1 = Item {
+            receipt: 0,
             description: "Iogurte Grego Natural Açucarado".into(),
-            quantity: 2,
+            category: "Frigorífico".into(),
+            quantity: 2.0,
+            unit: String::new(),
             price: 2.48,
             owners: Vec::new(),
+            flagged: false,
+            manual: false,
         };
         let item2 = Item {
+            receipt: 0,
             description: "Iogurte Grego Natural Açucarado".into(),
-            quantity: 1,
+            category: "Frigorífico".into(),
+            quantity: 1.0,
+            unit: String::new(),
             price: 1.24,
             owners: Vec::new(),
+            flagged: false,
+            manual: false,
         };
         AppState {
             focused: FocusedWindow::Items(0),
+            config: Config::default(),
+            view: View::default(),
+            show_help: false,
+            status: None,
+            session_path: None,
+            session_owned: false,
+            saved: String::new(),
+            autosave_path: None,
+            autosaved: String::new(),
+            receipt_files: Vec::new(),
+            receipt_currency: "EUR".into(),
+            last_receipt: 0,
+            last_panel: FocusedWindow::Items(0),
+            dialog: None,
             data: Data {
+                receipts: vec![Receipt {
+                    name: "receipt".into(),
+                    currency: "EUR".into(),
+                    expected_total: None,
+                    payer: None,
+                }],
                 items: vec![item1, item2],
                 people: vec!["jojo".into()],
+                charges: Vec::new(),
+                settlement_currency: "EUR".into(),
+                rates: ExchangeRates::new("EUR"),
             },
         }
     }
@@ -57,183 +230,156 @@ impl Default for AppState {
 
 impl AppState {
     pub fn with_data(data: Data) -> Self {
+        Self::with_config(data, Config::default())
+    }
+
+    pub fn with_config(data: Data, config: Config) -> Self {
         Self {
             focused: FocusedWindow::Items(0),
+            saved: session::to_json(&data),
+            autosaved: session::to_json(&data),
+            receipt_currency: config.format.currency.clone(),
+            last_receipt: 0,
+            last_panel: FocusedWindow::Items(0),
             data,
+            config,
+            view: View::default(),
+            show_help: false,
+            status: None,
+            session_path: None,
+            session_owned: false,
+            autosave_path: None,
+            receipt_files: Vec::new(),
+            dialog: None,
         }
     }
-}
 
-impl Data {
-    pub fn set_item_owner(&mut self, item_idx: usize, person_idx: usize) {
-        let owner = Owner {
-            person: person_idx,
-            percentage: 1.0,
-        };
+    // Whether anything changed since the session was loaded or saved
+    pub fn is_dirty(&self) -> bool {
+        session::to_json(&self.data) != self.saved
+    }
 
-        let size = self.items[item_idx].owners.len();
-        // Special case to avoid reallocation
-        if size == 1 {
-            self.items[item_idx].owners[0] = owner;
-        } else {
-            self.items[item_idx].owners = vec![owner];
+    pub fn save(&mut self) -> Result<&Path, String> {
+        let path = self.session_path.as_deref().ok_or("no session file")?;
+        let json = session::to_json(&self.data);
+        fs::write(path, &json).map_err(|err| format!("{}: {}", path.display(), err))?;
+        self.saved = json;
+        self.session_owned = true;
+        // Nothing left for it to recover
+        if let Some(autosave) = &self.autosave_path {
+            autosave::remove(autosave);
         }
+        Ok(path)
     }
 
-    pub fn set_item_owners(&mut self, item_idx: usize, owners: Vec<Owner>) {
-        self.items[item_idx].owners = owners;
+    // Brings the crash copy up to date after a change, removing it once
+    // there's nothing unsaved
+    pub fn autosave(&mut self) -> Result<(), String> {
+        let path = match &self.autosave_path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let json = session::to_json(&self.data);
+        if json == self.autosaved {
+            return Ok(());
+        }
+        if json == self.saved {
+            autosave::remove(path);
+        }
+        else {
+            autosave::write(path, &json)?;
+        }
+        self.autosaved = json;
+        Ok(())
     }
 
-    pub fn set_rest_items_owner(&mut self, person_idx: usize) {
-        for item in &mut self.items {
-            if item.owners.is_empty() {
-                item.owners.push(Owner {
-                    person: person_idx,
-                    percentage: 1.0,
-                })
-            }
+    pub fn discard_autosave(&mut self) {
+        if let Some(path) = &self.autosave_path {
+            autosave::remove(path);
         }
     }
 
-    pub fn compute_total(&self) -> Vec<f32> {
-        let mut totals = vec![0f32; self.people.len()];
-        for item in &self.items {
-            for owner in &item.owners {
-                totals[owner.person] += owner.percentage * item.price;
-            }
+    // Adds the receipts of another receipt or session file, returning
+    // the index of the first
+    pub fn import(&mut self, path: &Path) -> Result<usize, String> {
+        let data = session::load_file(path, &self.receipt_currency)?;
+        let first = self.data.merge(data);
+        self.receipt_files.resize(first, None);
+        self.receipt_files.resize(self.data.receipts.len(), receipt_file(path));
+        Ok(first)
+    }
+
+    // Takes in a new version of a receipt changed on disk, leaving
+    // whatever pointed at its items or charges
+    pub fn reload(&mut self, receipt_idx: usize, fresh: Data) {
+        if receipt_idx >= self.data.receipts.len() {
+            return;
         }
-        totals
-    }
-
-    pub fn load<P: AsRef<Path>>(filename: P) -> io::Result<Self> {
-        let mut items = Vec::<Item>::with_capacity(20);
-
-        use regex::Regex;
-        use std::fs::File;
-        use std::io::BufRead;
-        let file = File::open(filename)?;
-        let mut parser_state: u8 = 0;
-        // 0: Have to read description next
-        // 1: Have to read quantity next
-        // 2: Have to read discount next
-        // 3: Have to read price next
-
-        let mut current_item = Item {
-            description: String::from(""),
-            quantity: 0,
-            price: 0.0,
-            owners: Vec::new(),
+        let (flagged, removed) = self.data.reload_receipt(receipt_idx, fresh);
+        let last_item = self.data.items.len().saturating_sub(1);
+        let last_charge = self.data.charges.len().saturating_sub(1);
+        self.focused = match std::mem::replace(&mut self.focused, FocusedWindow::Items(0)) {
+            FocusedWindow::Items(idx)
+            | FocusedWindow::OwnerSelector(idx,_,_)
+            | FocusedWindow::SplitItem(idx,_)
+            | FocusedWindow::EditItem(idx,_,_) => FocusedWindow::Items(idx.min(last_item)),
+            FocusedWindow::Charges(idx)
+            | FocusedWindow::ChargeSplitSelector(idx,_,_) => FocusedWindow::Charges(idx.min(last_charge)),
+            focused => focused,
         };
-        for line in io::BufReader::new(file).lines() {
-            let line = line.unwrap();
-            match parser_state {
-                0 => {
-                    // 0. Item description (capture)
-                    let re = Regex::new(r"[ ]{4}(.+)").unwrap();
-                    if let Some(capture) = re.captures(&line) {
-                        current_item.description = capture.get(1).unwrap().as_str().to_string();
-                        parser_state = 1;
-                    }
-                }
-                1 => {
-                    // 1. Item quantity (capture)
-                    let re = Regex::new(r"[ ]{4}(\d+).*").unwrap();
-                    if let Some(capture) = re.captures(&line) {
-                        current_item.quantity =
-                            capture.get(1).unwrap().as_str().parse::<u32>().unwrap();
-                        parser_state = 2;
-                    } else {
-                        parser_state = 0;
-                    }
-                }
-                2 => {
-                    // 2. Item discount (match only)
-                    let re = Regex::new(r"[ ]{4}-?\d+,\d+.*").unwrap();
-                    if re.is_match(&line) {
-                        parser_state = 3;
-                    }
-                }
-                3 => {
-                    // 3. Item price (capture)
-                    let re = Regex::new(r"[ ]{4}(\d+),(\d+).*").unwrap();
-                    if let Some(capture) = re.captures(&line) {
-                        //
-                        let a = capture.get(1).unwrap().as_str().parse::<u32>().unwrap();
-                        let b = capture.get(2).unwrap().as_str().parse::<u32>().unwrap();
-                        current_item.price = (a as f32) + ((b as f32) * 0.01f32);
-                        if current_item.price != 0.0 {
-                            items.push(current_item);
-                            current_item = Item {
-                                description: String::from(""),
-                                quantity: 0,
-                                price: 0.0,
-                                owners: Vec::new(),
-                            }
-                        }
-                    }
-                    parser_state = 0;
-                }
-                _ => panic!("Unexpected data parser state"),
-            }
+        if matches!(self.dialog, Some(Dialog { action: DialogAction::RemoveItem(_) | DialogAction::RemoveCharge(_), .. })) {
+            self.dialog = None;
         }
-        Ok(Self {
-            items,
-            people: vec!["jojo".into()],
-        })
+        self.status = Some(format!(
+            "Reloaded {}: {} item{} new or changed, {} removed",
+            self.data.receipts[receipt_idx].name,
+            flagged,
+            if flagged == 1 { "" } else { "s" },
+            removed,
+        ));
     }
-}
 
-pub fn from_indices_to_owners(people: &Vec<usize>) -> Vec<Owner> {
-    let mut owners = Vec::with_capacity(people.len());
-    let percentage = 1f32 / (people.len() as f32);
-    for person in people {
-        owners.push(Owner {
-            person: *person,
-            percentage,
-        });
+    // Prompt for an item's field, holding its current value
+    pub fn item_field_input(&self, item_idx: usize, field: ItemField) -> TextInput {
+        let item = &self.data.items[item_idx];
+        match field {
+            ItemField::Description => TextInput::new().with_text(&item.description),
+            ItemField::Quantity => TextInput::numeric().with_text(&self.config.format.quantity(item.quantity, "")),
+            ItemField::Unit => TextInput::new().with_text(&item.unit),
+            ItemField::Price => TextInput::numeric().with_text(&self.config.format.editable(item.price)),
+            ItemField::Category => TextInput::new().with_text(&item.category),
+        }
     }
-    owners
-}
 
-// [{"jojo",0.2}, {"jojo",0.2}, {"jojo",0.2}, {"jojo",0.2}, {"bu",0.2}]
-// turns into
-// [{"jojo",0.8}, {"bu",0.2}]
-pub fn flatten_owners(owners: &mut Vec<Owner>) {
-    owners.dedup_by(|a, b| {
-        if a.person == b.person {
-            b.percentage += a.percentage;
-            true
-        } else {
-            false
-        }
-    });
+    // Closes a prompt, back on the row selected before it opened or the
+    // one now in its place
+    pub fn return_to_panel(&mut self) {
+        let last = |len: usize, idx: usize| idx.min(len.saturating_sub(1));
+        self.focused = match self.last_panel {
+            FocusedWindow::People(idx) => FocusedWindow::People(last(self.data.people.len(), idx)),
+            FocusedWindow::Charges(idx) => FocusedWindow::Charges(last(self.data.charges.len(), idx)),
+            FocusedWindow::Items(idx) => FocusedWindow::Items(last(self.data.items.len(), idx)),
+            _ => FocusedWindow::Items(0),
+        };
+    }
+
+    // Receipt of whatever is selected, for prompts acting on a receipt,
+    // or of what was last selected when it isn't an item or charge
+    pub fn current_receipt(&self) -> usize {
+        let selected = match self.focused {
+            FocusedWindow::Items(idx)
+            | FocusedWindow::OwnerSelector(idx,_,_)
+            | FocusedWindow::SplitItem(idx,_)
+            | FocusedWindow::EditItem(idx,_,_) =>
+                self.data.items.get(idx).map(|item| item.receipt),
+            FocusedWindow::Charges(idx) | FocusedWindow::ChargeSplitSelector(idx,_,_) =>
+                self.data.charges.get(idx).map(|charge| charge.receipt),
+            _ => None,
+        };
+        selected.unwrap_or(self.last_receipt).min(self.data.receipts.len() - 1)
+    }
 }
 
 pub fn owner_to_string(owner: &Owner, app: &AppState) -> String {
     app.data.people[owner.person].clone()
 }
-
-use tui::style::Color;
-pub fn person_color(person: usize) -> Color {
-    const VEC_COLORS: [Color; 14] = [
-        Color::Blue,
-        Color::Red,
-        Color::LightCyan,
-        Color::Green,
-        Color::Yellow,
-        Color::LightMagenta,
-        Color::Magenta,
-        Color::LightGreen,
-        Color::Cyan,
-        Color::LightYellow,
-        Color::Gray,
-        Color::LightBlue,
-        Color::LightRed,
-        Color::DarkGray,
-    ];
-    if person < 14 {
-        VEC_COLORS[person]
-    } else {
-        Color::Reset
-    }
-}
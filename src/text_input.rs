@@ -0,0 +1,153 @@
+// Single line text field used by every prompt, editing whole graphemes so
+// accents and emoji are never split
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use crate::event::{KeyCode, KeyEvent, KeyModifiers};
+
+pub struct TextInput {
+    text: String,
+    // Byte offset into `text`, always on a grapheme boundary
+    cursor: usize,
+    // Characters that can be typed, others are ignored
+    accepts: fn(char) -> bool,
+    // Why the text was refused, shown until it's edited
+    pub error: Option<String>,
+}
+
+impl Default for TextInput {
+    fn default() -> Self {
+        TextInput::new()
+    }
+}
+
+impl TextInput {
+    pub fn new() -> Self {
+        TextInput {
+            text: String::new(),
+            cursor: 0,
+            accepts: |c| !c.is_control(),
+            error: None,
+        }
+    }
+
+    // Amounts and quantities, e.g. "1234,56"
+    pub fn numeric() -> Self {
+        TextInput {
+            accepts: |c| c.is_ascii_digit() || c == ',' || c == '.',
+            ..TextInput::new()
+        }
+    }
+
+    // Starts with `text`, the cursor at its end
+    pub fn with_text(mut self, text: &str) -> Self {
+        self.text.clear();
+        self.cursor = 0;
+        self.insert(text);
+        self
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    // Columns taken by the text before the cursor
+    pub fn cursor_width(&self) -> usize {
+        self.text[..self.cursor].width()
+    }
+
+    // Inserts typed or pasted text at the cursor. Line breaks and tabs
+    // become spaces, anything else not accepted is dropped.
+    pub fn insert(&mut self, text: &str) {
+        let accepts = self.accepts;
+        let text: String = text.chars()
+            .map(|c| if c == '\n' || c == '\r' || c == '\t' { ' ' } else { c })
+            .filter(|c| accepts(*c))
+            .collect();
+        if text.is_empty() {
+            return;
+        }
+        self.text.insert_str(self.cursor, &text);
+        self.cursor += text.len();
+        self.error = None;
+    }
+
+    // Edits the text if the key is an editing key, returns whether it was
+    pub fn handle(&mut self, key: &KeyEvent) -> bool {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let word = ctrl || key.modifiers.contains(KeyModifiers::ALT);
+        match key.code {
+            KeyCode::Char(c) if !word => self.insert(&c.to_string()),
+            KeyCode::Char('w') if ctrl => self.delete_to(self.word_start()),
+            KeyCode::Char('u') if ctrl => self.delete_to(0),
+            KeyCode::Char('k') if ctrl => self.delete_to(self.text.len()),
+            KeyCode::Char('a') if ctrl => self.cursor = 0,
+            KeyCode::Char('e') if ctrl => self.cursor = self.text.len(),
+            KeyCode::Backspace if word => self.delete_to(self.word_start()),
+            KeyCode::Backspace => self.delete_to(self.previous()),
+            KeyCode::Delete => self.delete_to(self.next()),
+            KeyCode::Left if word => self.cursor = self.word_start(),
+            KeyCode::Right if word => self.cursor = self.word_end(),
+            KeyCode::Left => self.cursor = self.previous(),
+            KeyCode::Right => self.cursor = self.next(),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.text.len(),
+            _ => return false,
+        }
+        true
+    }
+
+    // Removes the text between the cursor and `position`
+    fn delete_to(&mut self, position: usize) {
+        let (start, end) = if position < self.cursor { (position, self.cursor) } else { (self.cursor, position) };
+        if start != end {
+            self.text.replace_range(start..end, "");
+            self.cursor = start;
+            self.error = None;
+        }
+    }
+
+    fn previous(&self) -> usize {
+        self.text[..self.cursor]
+            .grapheme_indices(true)
+            .next_back()
+            .map_or(0, |(i, _)| i)
+    }
+
+    fn next(&self) -> usize {
+        self.text[self.cursor..]
+            .graphemes(true)
+            .next()
+            .map_or(self.cursor, |grapheme| self.cursor + grapheme.len())
+    }
+
+    // Start of the word before the cursor, skipping spaces first
+    fn word_start(&self) -> usize {
+        let mut start = self.cursor;
+        let mut in_word = false;
+        for (i, grapheme) in self.text[..self.cursor].grapheme_indices(true).rev() {
+            let space = grapheme.trim().is_empty();
+            if space && in_word {
+                break;
+            }
+            in_word |= !space;
+            start = i;
+        }
+        start
+    }
+
+    // End of the word after the cursor, skipping spaces first
+    fn word_end(&self) -> usize {
+        let mut end = self.cursor;
+        let mut in_word = false;
+        for grapheme in self.text[self.cursor..].graphemes(true) {
+            let space = grapheme.trim().is_empty();
+            if space && in_word {
+                break;
+            }
+            in_word |= !space;
+            end += grapheme.len();
+        }
+        end
+    }
+}
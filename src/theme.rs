@@ -0,0 +1,227 @@
+use std::env;
+use tui::style::{Color, Modifier, Style};
+
+// How many colours the terminal can show
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorDepth {
+    Ansi16,
+    Ansi256,
+    TrueColor,
+}
+
+impl ColorDepth {
+    // Guessed from COLORTERM and TERM
+    pub fn detect() -> Self {
+        let colorterm = env::var("COLORTERM").unwrap_or_default();
+        let term = env::var("TERM").unwrap_or_default();
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            ColorDepth::TrueColor
+        }
+        else if term.contains("256color") {
+            ColorDepth::Ansi256
+        }
+        else {
+            ColorDepth::Ansi16
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "auto" => Some(ColorDepth::detect()),
+            "16" => Some(ColorDepth::Ansi16),
+            "256" => Some(ColorDepth::Ansi256),
+            "truecolor" | "24bit" => Some(ColorDepth::TrueColor),
+            _ => None,
+        }
+    }
+}
+
+pub struct Theme {
+    // Row under the cursor
+    pub selected: Style,
+    // Item waiting for an owner to be chosen
+    pub pending: Style,
+    // Border and title of the focused panel
+    pub focus: Style,
+    // Secondary lines, like each person's charges
+    pub muted: Style,
+    pub ok: Style,
+    pub error: Style,
+    // Text over a person's colour
+    pub person_fg: Option<Color>,
+    // First people's colours, the rest are generated. Empty for no colour.
+    pub palette: Vec<Color>,
+    pub depth: ColorDepth,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        // https://no-color.org
+        let no_color = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+        Theme::preset(if no_color { "mono" } else { "dark" }).unwrap()
+    }
+}
+
+impl Theme {
+    pub fn preset(name: &str) -> Option<Self> {
+        let theme = match name {
+            "dark" => Theme {
+                selected: Style::default().bg(Color::White).fg(Color::Black),
+                pending: Style::default().bg(Color::LightYellow).fg(Color::Black),
+                focus: Style::default().fg(Color::Yellow),
+                muted: Style::default().fg(Color::DarkGray),
+                ok: Style::default().fg(Color::Green),
+                error: Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+                person_fg: Some(Color::White),
+                palette: vec![
+                    Color::Blue,
+                    Color::Red,
+                    Color::LightCyan,
+                    Color::Green,
+                    Color::Yellow,
+                    Color::LightMagenta,
+                    Color::Magenta,
+                    Color::LightGreen,
+                    Color::Cyan,
+                    Color::LightYellow,
+                    Color::Gray,
+                    Color::LightBlue,
+                    Color::LightRed,
+                    Color::DarkGray,
+                ],
+                depth: ColorDepth::detect(),
+            },
+            "light" => Theme {
+                selected: Style::default().bg(Color::Black).fg(Color::White),
+                pending: Style::default().bg(Color::Yellow).fg(Color::Black),
+                focus: Style::default().fg(Color::Blue),
+                muted: Style::default().fg(Color::Gray),
+                ok: Style::default().fg(Color::Green),
+                error: Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+                person_fg: Some(Color::Black),
+                palette: vec![
+                    Color::LightBlue,
+                    Color::LightRed,
+                    Color::LightCyan,
+                    Color::LightGreen,
+                    Color::LightYellow,
+                    Color::LightMagenta,
+                    Color::Cyan,
+                    Color::Yellow,
+                    Color::Magenta,
+                    Color::Green,
+                    Color::Gray,
+                ],
+                depth: ColorDepth::detect(),
+            },
+            // Only text attributes, no colour at all
+            "mono" => Theme {
+                selected: Style::default().add_modifier(Modifier::REVERSED),
+                pending: Style::default().add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+                focus: Style::default().add_modifier(Modifier::BOLD),
+                muted: Style::default().add_modifier(Modifier::DIM),
+                ok: Style::default(),
+                error: Style::default().add_modifier(Modifier::BOLD),
+                person_fg: None,
+                palette: Vec::new(),
+                depth: ColorDepth::Ansi16,
+            },
+            _ => return None,
+        };
+        Some(theme)
+    }
+
+    pub fn person_color(&self, person: usize) -> Option<Color> {
+        if self.palette.is_empty() {
+            return None;
+        }
+        if person < self.palette.len() {
+            return Some(self.palette[person]);
+        }
+        let generated = person - self.palette.len();
+        Some(match self.depth {
+            ColorDepth::TrueColor => {
+                let (r, g, b) = generated_rgb(generated);
+                Color::Rgb(r, g, b)
+            }
+            ColorDepth::Ansi256 => {
+                // Closest entry in the 6x6x6 colour cube
+                let (r, g, b) = generated_rgb(generated);
+                let level = |c: u8| (c as u16 * 5 / 255) as u8;
+                Color::Indexed(16 + 36 * level(r) + 6 * level(g) + level(b))
+            }
+            ColorDepth::Ansi16 => self.palette[person % self.palette.len()],
+        })
+    }
+
+    // Background of a person's row
+    pub fn person(&self, person: usize) -> Style {
+        match self.person_color(person) {
+            Some(color) => Style::default().bg(color),
+            None => Style::default(),
+        }
+    }
+
+    // Person's name tag in the items' owner column
+    pub fn owner_tag(&self, person: usize) -> Style {
+        match self.person_fg {
+            Some(fg) => self.person(person).fg(fg),
+            None => self.person(person),
+        }
+    }
+}
+
+// Spreads hues by the golden angle so neighbouring people never get
+// similar colours, alternating lightness for extra contrast
+fn generated_rgb(n: usize) -> (u8, u8, u8) {
+    let hue = (n as f32 * 137.508) % 360.0;
+    let lightness = if n.is_multiple_of(2) { 0.45 } else { 0.6 };
+    hsl_to_rgb(hue, 0.65, lightness)
+}
+
+fn hsl_to_rgb(hue: f32, saturation: f32, lightness: f32) -> (u8, u8, u8) {
+    let c = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let x = c * (1.0 - ((hue / 60.0) % 2.0 - 1.0).abs());
+    let m = lightness - c / 2.0;
+    let (r, g, b) = match hue as u32 {
+        0..=59 => (c, x, 0.0),
+        60..=119 => (x, c, 0.0),
+        120..=179 => (0.0, c, x),
+        180..=239 => (0.0, x, c),
+        240..=299 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    let to_byte = |v: f32| ((v + m) * 255.0).round() as u8;
+    (to_byte(r), to_byte(g), to_byte(b))
+}
+
+// Parses "blue", "light-red", "#ff8800" or a 256-colour index
+pub fn parse_color(text: &str) -> Option<Color> {
+    let name = text.to_lowercase().replace(['-', '_', ' '], "");
+    let color = match name.as_str() {
+        "reset" | "default" => Color::Reset,
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" | "grey" => Color::Gray,
+        "darkgray" | "darkgrey" => Color::DarkGray,
+        "lightred" => Color::LightRed,
+        "lightgreen" => Color::LightGreen,
+        "lightyellow" => Color::LightYellow,
+        "lightblue" => Color::LightBlue,
+        "lightmagenta" => Color::LightMagenta,
+        "lightcyan" => Color::LightCyan,
+        "white" => Color::White,
+        // Sliced by bytes, which only lands on characters in ASCII
+        hex if hex.starts_with('#') && hex.len() == 7 && hex.is_ascii() => {
+            let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+            Color::Rgb(channel(1)?, channel(3)?, channel(5)?)
+        }
+        index => Color::Indexed(index.parse().ok()?),
+    };
+    Some(color)
+}
@@ -1,274 +1,766 @@
 use tui::{
     Frame,
     backend::Backend,
+    buffer::Buffer,
     layout::{
-        Constraint, Direction, Layout
+        Constraint, Direction, Layout, Rect,
     },
     widgets::{
-        Block, Borders, Wrap, Paragraph,
-        Cell, Row, Table, TableState, BorderType,
+        Block, Borders, Paragraph,
+        Cell, Row, Table, TableState, BorderType, Clear, Widget,
     },
     style::{
-        Color, Modifier, Style
+        Modifier, Style
     },
     text::{
         Spans,
         Span,
+        Text,
     },
 };
 
 use crate::state::*;
+use crate::keymap::{Action, Keymap};
+use crate::theme::Theme;
+use crate::text_input::TextInput;
+use crate::input_handlers::{window_actions, DIALOG_ACTIONS};
 
 
 // Main UI render
 pub fn ui<B: Backend>(f: &mut Frame<B>, app: &AppState) {
-    let chunks = Layout::default()
-        .direction(Direction::Horizontal)
-        // .margin(1)
-        .constraints([Constraint::Percentage(80), Constraint::Percentage(20)].as_ref())
+    let theme = &app.config.theme;
+    let format = &app.config.format;
+
+    // Key hints footer below everything else
+    let screen = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(1)].as_ref())
         .split(f.size());
 
-    
-    let side_chunks = if let FocusedWindow::AddPerson(_) = app.focused {
-        Layout::default()
+    // While working on the items the side panels can be hidden, unless
+    // a prompt needs them
+    let side_hidden = app.view.sidebar_collapsed
+        && matches!(app.focused, FocusedWindow::Items(_) | FocusedWindow::PersonDetail(_,_));
+    let narrow = screen[0].width < NARROW_WIDTH;
+    let (main_area, side_chunks) = if side_hidden {
+        (screen[0], None)
+    }
+    else if narrow {
+        // Side panels side by side below the items
+        let side_height = (screen[0].height * 2 / 5).clamp(8, 14);
+        let chunks = Layout::default()
             .direction(Direction::Vertical)
-            .constraints([Constraint::Percentage(20), Constraint::Percentage(85)].as_ref())
-            .split(chunks[1])
+            .constraints([Constraint::Min(0), Constraint::Length(side_height)].as_ref())
+            .split(screen[0]);
+        (chunks[0], Some(side_layout(app, chunks[1], true)))
     }
     else {
-        Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([Constraint::Percentage(0), Constraint::Percentage(100)].as_ref())
-            .split(chunks[1])
+        let side_width = (screen[0].width * 3 / 10).clamp(30, 48);
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Min(0), Constraint::Length(side_width)].as_ref())
+            .split(screen[0]);
+        (chunks[0], Some(side_layout(app, chunks[1], false)))
     };
-    
 
-    
     /////////////// Render people table /////////////
 
     let totals = app.data.compute_total();
+    let subtotals = app.data.receipt_subtotals();
+    let charges_shares: Vec<Vec<f32>> = app.data.charges.iter()
+        .map(|charge| app.data.charge_shares(charge, &subtotals[charge.receipt]))
+        .collect();
 
+    // Charge lines are interleaved with people, so keep track of
+    // which row each person is rendered in
+    let mut person_rows = Vec::with_capacity(app.data.people.len());
     let mut people_rows = Vec::with_capacity(app.data.people.len());
     for (i,person) in app.data.people.iter().enumerate() {
+        person_rows.push(people_rows.len());
+        // Whoever paid is marked, along with what they paid when there
+        // are several receipts
+        let paid: Vec<&str> = app.data.receipts.iter()
+            .filter(|receipt| receipt.payer == Some(i))
+            .map(|receipt| receipt.name.as_str())
+            .collect();
+        let name = match paid.is_empty() {
+            true => person.clone(),
+            false if app.data.receipts.len() == 1 => format!("{} $", person),
+            false => format!("{} $ {}", person, paid.join(", ")),
+        };
         // Get row selected or not
         let row = match app.focused {
             // Selected
-            FocusedWindow::People(idx) | FocusedWindow::OwnerSelector(_,idx,_) if i == idx => 
+            FocusedWindow::People(idx)
+            | FocusedWindow::OwnerSelector(_,idx,_)
+            | FocusedWindow::ChargeSplitSelector(_,idx,_) if i == idx =>
                 Row::new(vec![
-                    Cell::from(person.as_ref()),
-                    Cell::from(totals[i].to_string()),
+                    Cell::from(name),
+                    Cell::from(format.money_in(totals[i], &app.data.settlement_currency)),
                 ])
-                .style(Style::default().bg(Color::White).fg(Color::Black)),
+                .style(theme.selected),
             // Normal
             _ => 
                 Row::new(vec![
-                    Cell::from(person.as_ref()),
-                    Cell::from(totals[i].to_string()),
+                    Cell::from(name),
+                    Cell::from(format.money_in(totals[i], &app.data.settlement_currency)),
                 ])
-                .style(Style::default().bg(person_color(i))/* .fg(Color::Black) */),
+                .style(theme.person(i)),
         };
         people_rows.push(row);
+
+        // Separate line for each charge this person takes part in
+        for (charge, shares) in app.data.charges.iter().zip(&charges_shares) {
+            if shares[i] != 0.0 {
+                people_rows.push(
+                    Row::new(vec![
+                        Cell::from(format!(
+                            " {} {}",
+                            if charge.is_credit() { "-" } else { "+" },
+                            charge.description,
+                        )),
+                        Cell::from(format.money_in(shares[i], &app.data.settlement_currency)),
+                    ])
+                    .style(theme.muted)
+                );
+            }
+        }
     }
     
+    // Names take whatever the amounts leave
+    let people_width = side_chunks.as_ref().map_or(0, |chunks| chunks[1].width);
+    let people_widths = [
+        Constraint::Length(people_width.saturating_sub(AMOUNT_WIDTH + 3).max(8)),
+        Constraint::Length(AMOUNT_WIDTH),
+    ];
+
     // Create List and customize layout
     let people_list = Table::new(people_rows)
-        .block(
-            match app.focused {
-                FocusedWindow::OwnerSelector(_,_,_) | FocusedWindow::People(_) =>
-                    Block::default()
-                        .borders(Borders::ALL)
-                        .title("People")
-                        .border_type(BorderType::Thick),
-                _ => Block::default()
-                    .borders(Borders::ALL)
-                    .title("People"),
-            }
-        )
-        .widths(&[
-            Constraint::Percentage(60),
-            Constraint::Percentage(40),
-        ]);
+        .block(panel_block("People", matches!(
+            app.focused,
+            FocusedWindow::OwnerSelector(_,_,_)
+            | FocusedWindow::ChargeSplitSelector(_,_,_)
+            | FocusedWindow::People(_)
+            | FocusedWindow::PersonDetail(_,_)
+        ), theme))
+        .widths(&people_widths);
 
     /////////////// Render items table /////////////
+
+    // Fixed numeric columns, the description takes whatever is left.
+    // Which receipt each item is from only matters with several.
+    let several_receipts = app.data.receipts.len() > 1;
+    let receipt_width = if several_receipts { RECEIPT_WIDTH } else { 0 };
+    let items_inner_width = main_area.width.saturating_sub(2);
+    let owner_width = (items_inner_width * 3 / 10).max(8);
+    let description_width = items_inner_width
+        .saturating_sub(receipt_width + QUANTITY_WIDTH + PRICE_WIDTH + owner_width + 3 + several_receipts as u16)
+        .max(1);
+
+    let mut items_widths = vec![Constraint::Length(description_width)];
+    if several_receipts {
+        items_widths.push(Constraint::Length(receipt_width));
+    }
+    items_widths.extend([
+        Constraint::Length(QUANTITY_WIDTH),
+        Constraint::Length(PRICE_WIDTH),
+        Constraint::Length(owner_width),
+    ]);
+
     let mut item_rows = Vec::with_capacity(app.data.items.len());
+    let mut item_heights = Vec::with_capacity(app.data.items.len());
 
     for (i,item) in app.data.items.iter().enumerate() {
+        // Items a reload brought in or changed are marked until assigned
+        let marker = if item.flagged { FLAG_MARKER } else { "" };
+        let text_width = (description_width as usize).saturating_sub(marker.chars().count()).max(1);
+        let description = if app.view.wrap_descriptions {
+            wrap_text(&item.description, text_width)
+        }
+        else {
+            vec![clip_text(&item.description, app.view.description_offset, text_width)]
+        };
+        let height = description.len().max(1) as u16;
+        item_heights.push(height);
+
+        let mut spans = Vec::with_capacity(item.owners.len());
+        for owner in &item.owners {
+            spans.push(
+                Span::styled(
+                    if owner.percentage == 1f32 {
+                        format!(" {} ", app.data.people[owner.person])
+                    }
+                    else {
+                        format!(" {} {:.2} ", app.data.people[owner.person], owner.percentage)
+                    },
+                    theme.owner_tag(owner.person)
+                )
+            );
+        }
+        let mut description: Vec<Spans> = description.into_iter().map(Spans::from).collect();
+        if let Some(first) = description.first_mut().filter(|_| item.flagged) {
+            first.0.insert(0, Span::styled(marker, theme.focus.add_modifier(Modifier::BOLD)));
+        }
+        let mut cells = vec![Cell::from(Text::from(description))];
+        if several_receipts {
+            let name = &app.data.receipts[item.receipt].name;
+            cells.push(Cell::from(Span::styled(clip_text(name, 0, receipt_width as usize), theme.muted)));
+        }
+        let row = Row::new(cells.into_iter().chain([
+            Cell::from(format.quantity(item.quantity, &item.unit)),
+            Cell::from(format.money_in(item.price, &app.data.receipts[item.receipt].currency)),
+            Cell::from(Spans::from(spans)),
+        ]))
+        .height(height);
+
         // Get row selected or not
         let row = match app.focused {
             // Selected
-            FocusedWindow::Items(idx) if i == idx =>  {
-                Row::new(vec![
-                        Cell::from(item.description.as_ref()),
-                        Cell::from(item.quantity.to_string()),
-                        Cell::from(item.price.to_string()),
-                        Cell::from(
-                            Spans::from({
-                                let mut spans = Vec::with_capacity(item.owners.len());
-                                for owner in &item.owners {
-                                    spans.push(
-                                        Span::styled(
-                                            if owner.percentage == 1f32 {
-                                                format!(" {} ", app.data.people[owner.person])
-                                            }
-                                            else {
-                                                format!(" {} {:.2} ", app.data.people[owner.person], owner.percentage)
-                                            },
-                                            Style::default().bg(person_color(owner.person)).fg(Color::White)
-                                        )
-                                    );
-                                    
-                                }
-                                spans
-                            })
-                        ),
-                    ]
-                )
-                .style(Style::default().bg(Color::White).fg(Color::Black))
-            },
+            FocusedWindow::Items(idx) if i == idx => row.style(theme.selected),
             // Select respective owner
-            FocusedWindow::OwnerSelector(idx,_,_) if i == idx => {
-                Row::new(vec![
-                        Cell::from(item.description.as_ref()),
-                        Cell::from(item.quantity.to_string()),
-                        Cell::from(item.price.to_string()),
-                        Cell::from(
-                            Spans::from({
-                                let mut spans = Vec::with_capacity(item.owners.len());
-                                for owner in &item.owners {
-                                    spans.push(
-                                        Span::styled(
-                                            if owner.percentage == 1f32 {
-                                                format!(" {} ", app.data.people[owner.person])
-                                            }
-                                            else {
-                                                format!(" {} {:.2} ", app.data.people[owner.person], owner.percentage)
-                                            },
-                                            Style::default().bg(person_color(owner.person)).fg(Color::White)
-                                        )
-                                    );
-                                    
-                                }
-                                spans
-                            })
-                        ),
-                    ]
-                )
-                .style(Style::default().bg(Color::LightYellow).fg(Color::Black))
-            },
+            FocusedWindow::OwnerSelector(idx,_,_)
+            | FocusedWindow::SplitItem(idx,_)
+            | FocusedWindow::EditItem(idx,_,_) if i == idx => row.style(theme.pending),
             // Normal
-            _ => {
-                Row::new(vec![
-                    Cell::from(item.description.as_ref()),
-                    Cell::from(item.quantity.to_string()),
-                    Cell::from(item.price.to_string()),
-                    Cell::from(
-                        Spans::from({
-                            let mut spans = Vec::with_capacity(item.owners.len());
-                            for owner in &item.owners {
-                                spans.push(
-                                    Span::styled(
-                                        if owner.percentage == 1f32 {
-                                            format!(" {} ", app.data.people[owner.person])
-                                        }
-                                        else {
-                                            format!(" {} {:.2} ", app.data.people[owner.person], owner.percentage)
-                                        },
-                                        Style::default().bg(person_color(owner.person)).fg(Color::White)
-                                    )
-                                );
-                                
-                            }
-                            spans
-                        })
-                    ),
-                ])
-            },
+            _ => row,
         };
         item_rows.push(row);
     }
 
+    let mut item_headers = vec!["Description"];
+    if several_receipts {
+        item_headers.push("Receipt");
+    }
+    item_headers.extend(["Quantity", "Price", "Owner"]);
+
     // Create Table and customize layout
     let items_table = Table::new(item_rows)
-    .block(
-        if let FocusedWindow::Items(_) = app.focused {
-            Block::default()
-                .borders(Borders::ALL)
-                .title("Items")
-                .border_type(BorderType::Thick)
-        }
-        else {
-            Block::default()
-                .borders(Borders::ALL)
-                .title("Items")
-        }
-    )
+    .block(panel_block("Items", matches!(app.focused, FocusedWindow::Items(_)), theme))
     .header(
-        Row::new(vec![
-            Cell::from(Span::styled(
-                "Description",
-                Style::default().add_modifier(Modifier::BOLD),
-            )),
-            Cell::from(Span::styled(
-                "Quantity",
-                Style::default().add_modifier(Modifier::BOLD),
-            )),
-            Cell::from(Span::styled(
-                "Price",
-                Style::default().add_modifier(Modifier::BOLD),
-            )),
-            Cell::from(Span::styled(
-                "Owner",
-                Style::default().add_modifier(Modifier::BOLD),
-            )),
-        ]).height(2),
+        Row::new(item_headers.into_iter().map(|header| Cell::from(Span::styled(
+            header,
+            Style::default().add_modifier(Modifier::BOLD),
+        )))).height(2),
     )
-    .widths(&[
-        Constraint::Percentage(55),
-        Constraint::Percentage(10),
-        Constraint::Percentage(15),
-        Constraint::Percentage(20),
-    ]);
+    .widths(&items_widths);
 
-    /////////////// Render add person prompt ///////////////
-    
-    let new_person_name = if let FocusedWindow::AddPerson(name) = &app.focused {
-        name.as_ref()
-    }
-    else {
+    /////////////// Render input prompt ///////////////
+
+    let prompt_title = match &app.focused {
+        FocusedWindow::AddPerson(_) => "New Person".into(),
+        FocusedWindow::SetTotal(_,_) => "Receipt Total".into(),
+        FocusedWindow::AddCharge(_,_) => "New Charge (description amount)".into(),
+        FocusedWindow::AddCredit(_,_) => "New Voucher (description amount)".into(),
+        FocusedWindow::SetRate(receipt_idx, _) => {
+            format!("1 {} in {}", app.data.receipts[*receipt_idx].currency, app.data.settlement_currency)
+        }
+        FocusedWindow::AddItem(_,_) => "New Item (description price)".into(),
+        FocusedWindow::ImportReceipt(_) => "Import Receipt (path)".into(),
+        FocusedWindow::SplitItem(item_idx, _) => format!(
+            "Split off (of {})",
+            format.quantity(app.data.items[*item_idx].quantity, &app.data.items[*item_idx].unit),
+        ),
+        FocusedWindow::EditItem(_, field, _) => format!("Item {}", field.name()),
         // This will never be rendered
-        ""
+        _ => String::new(),
     };
 
-    let add_person_prompt = Paragraph::new(new_person_name.as_ref())
-        .block(Block::default()
-            .borders(Borders::ALL)
-            .title("New Person")
-            .border_type(BorderType::Thick)
-        )
-        // .alignment(Alignment::Center)
-        .wrap(Wrap { trim: true });
-    
+    /////////////// Render receipt reconciliation ///////////////
+
+    // Amounts of the selected receipt, in its own currency
+    let receipt_idx = app.current_receipt();
+    let currency = app.data.receipts[receipt_idx].currency.as_str();
+    // With several receipts, what each comes to first, the selected one
+    // in bold
+    let mut reconciliation_lines = Vec::new();
+    if app.data.receipts.len() > 1 {
+        for (i, receipt) in app.data.receipts.iter().enumerate() {
+            let style = if i == receipt_idx { Style::default().add_modifier(Modifier::BOLD) } else { theme.muted };
+            reconciliation_lines.push(Spans::from(Span::styled(
+                format!("{}: {}", receipt.name, format.money_in(app.data.receipt_total(i), &receipt.currency)),
+                style,
+            )));
+        }
+    }
+    reconciliation_lines.extend([
+        Spans::from(format!("Items:   {}", format.money_in(app.data.items_total(receipt_idx), currency))),
+        Spans::from(format!("Charges: {}", format.money_in(app.data.charges_total(receipt_idx), currency))),
+        Spans::from(format!("Credits: {}", format.money_in(app.data.credits_total(receipt_idx), currency))),
+    ]);
+    match (app.data.receipts[receipt_idx].expected_total, app.data.reconciliation(receipt_idx)) {
+        (Some(expected), Some(difference)) => {
+            reconciliation_lines.push(Spans::from(format!("Receipt: {}", format.money_in(expected, currency))));
+            // Differences under half a cent are rounding noise
+            reconciliation_lines.push(if difference.abs() < 0.005 {
+                Spans::from(Span::styled("Matches", theme.ok))
+            }
+            else {
+                Spans::from(Span::styled(
+                    format!("Diff:    {}", format.signed_money_in(difference, currency)),
+                    theme.error,
+                ))
+            });
+        },
+        _ => {
+            let keys = keys_to_string(&app.config.keymap, Action::SetTotal);
+            reconciliation_lines.push(Spans::from(format!("Receipt: press {}", keys)));
+        },
+    }
+    if currency != app.data.settlement_currency {
+        let settlement = app.data.settlement_currency.as_str();
+        reconciliation_lines.push(match app.data.rates.convert(1.0, currency, settlement) {
+            Some(rate) => Spans::from(format!("1 {} = {} {}", currency, format.rate(rate), settlement)),
+            None => Spans::from(Span::styled(
+                format!("No {} rate: press {}", currency, keys_to_string(&app.config.keymap, Action::SetRate)),
+                theme.error,
+            )),
+        });
+    }
+    let receipt_title = if app.data.receipts.len() > 1 { "Receipts" } else { "Receipt" };
+    let reconciliation = Paragraph::new(reconciliation_lines)
+        .block(panel_block(receipt_title, false, theme));
+
+    /////////////// Render charges table ///////////////
+
+    let charge_rows: Vec<Row> = app.data.charges.iter()
+        .map(|charge| Row::new(vec![
+            Cell::from(charge.description.as_ref()),
+            Cell::from(format.money_in(charge.amount, &app.data.receipts[charge.receipt].currency)),
+            Cell::from(split_to_string(&charge.split, app)),
+        ]))
+        .collect();
+    let charges_focused = matches!(
+        app.focused,
+        FocusedWindow::Charges(_) | FocusedWindow::ChargeSplitSelector(_,_,_)
+    );
+    let charges_table = Table::new(charge_rows)
+        .block(panel_block("Charges", charges_focused, theme))
+        .highlight_style(theme.selected)
+        .widths(&[
+            Constraint::Percentage(45),
+            Constraint::Percentage(20),
+            Constraint::Percentage(35),
+        ]);
+
     ////////////////////////////////////////////////
 
     // Widgets state updates
     let mut items_state = TableState::default();
     let mut people_state = TableState::default();
+    let mut charges_state = TableState::default();
     match app.focused {
         FocusedWindow::Items(idx) => {
             items_state.select(Some(idx));
         },
         FocusedWindow::OwnerSelector(item_idx, person_idx,_) => {
             items_state.select(Some(item_idx));
-            people_state.select(Some(person_idx));
+            people_state.select(person_rows.get(person_idx).copied());
         },
         FocusedWindow::People(idx) => {
-            people_state.select(Some(idx));
+            people_state.select(person_rows.get(idx).copied());
+        },
+        FocusedWindow::ChargeSplitSelector(charge_idx, person_idx,_) => {
+            charges_state.select(Some(charge_idx));
+            people_state.select(person_rows.get(person_idx).copied());
+        },
+        FocusedWindow::Charges(idx) if idx < app.data.charges.len() => {
+            charges_state.select(Some(idx));
         },
         _ => {},
     };
-    f.render_stateful_widget(items_table, chunks[0], &mut items_state);
-    f.render_stateful_widget(people_list, side_chunks[1], &mut people_state);
-    f.render_widget(add_person_prompt, side_chunks[0]);
+    // The person breakdown takes the place of the items table
+    if let FocusedWindow::PersonDetail(person_idx, row) = app.focused {
+        let mut detail_state = TableState::default();
+        detail_state.select(Some(row));
+        people_state.select(person_rows.get(person_idx).copied());
+        f.render_stateful_widget(person_detail_table(app, person_idx), main_area, &mut detail_state);
+    }
+    else {
+        let selected = items_state.selected();
+        f.render_stateful_widget(items_table, main_area, &mut items_state);
+        // Border, and the header with its blank line
+        let track = Rect {
+            x: main_area.right().saturating_sub(1),
+            y: main_area.y + 3,
+            width: 1,
+            height: main_area.height.saturating_sub(4),
+        };
+        f.render_widget(Scrollbar::new(&item_heights, selected.unwrap_or(0), theme.focus), track);
+    }
+    if let Some(side_chunks) = side_chunks {
+        f.render_stateful_widget(people_list, side_chunks[1], &mut people_state);
+        if let Some(input) = app.focused.text_input() {
+            render_prompt(f, input, &prompt_title, side_chunks[0], theme);
+        }
+        f.render_stateful_widget(charges_table, side_chunks[2], &mut charges_state);
+        f.render_widget(reconciliation, side_chunks[3]);
+    }
+    f.render_widget(footer(app), screen[1]);
+
+    if app.show_help {
+        let area = centered_rect(60, 70, f.size());
+        f.render_widget(Clear, area);
+        f.render_widget(help_table(app), area);
+    }
+    if let Some(dialog) = &app.dialog {
+        render_dialog(f, dialog, app);
+    }
 
     // f.render_widget(people_list, chunks[1]);
-}
\ No newline at end of file
+}
+
+// Below this many columns the side panels go under the items
+const NARROW_WIDTH: u16 = 100;
+const FLAG_MARKER: &str = "● ";
+const RECEIPT_WIDTH: u16 = 12;
+const QUANTITY_WIDTH: u16 = 8;
+const PRICE_WIDTH: u16 = 10;
+const AMOUNT_WIDTH: u16 = 11;
+
+// Prompt, People, Charges and Receipt areas
+fn side_layout(app: &AppState, area: Rect, narrow: bool) -> Vec<Rect> {
+    // A line more for why the text was refused
+    let prompt_height = Constraint::Length(match app.focused.text_input() {
+        Some(input) if input.error.is_some() => 4,
+        Some(_) => 3,
+        None => 0,
+    });
+    if narrow {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([prompt_height, Constraint::Min(0)].as_ref())
+            .split(area);
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(45), Constraint::Min(0), Constraint::Length(22)].as_ref())
+            .split(rows[1]);
+        vec![rows[0], columns[0], columns[1], columns[2]]
+    }
+    else {
+        // One line per charge, with room for at least one
+        let charges_height = Constraint::Length(app.data.charges.len().max(1) as u16 + 2);
+        // Extra line for the exchange rate of foreign receipts
+        let receipt_height = if app.data.receipts.iter().all(|receipt| receipt.currency == app.data.settlement_currency) {
+            7
+        }
+        else {
+            8
+        };
+        // And one per receipt when there are several
+        let receipt_height = receipt_height + if app.data.receipts.len() > 1 { app.data.receipts.len() as u16 } else { 0 };
+        Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([prompt_height, Constraint::Min(0), charges_height, Constraint::Length(receipt_height)].as_ref())
+            .split(area)
+    }
+}
+
+// Text field scrolled to keep the cursor in view, with the terminal's
+// cursor placed on it
+fn render_prompt<B: Backend>(f: &mut Frame<B>, input: &TextInput, title: &str, area: Rect, theme: &Theme) {
+    let block = panel_block(title, true, theme);
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    if inner.width == 0 || inner.height == 0 {
+        return;
+    }
+    let offset = (input.cursor_width() + 1).saturating_sub(inner.width as usize);
+    let text = Paragraph::new(input.text().to_string()).scroll((0, offset as u16));
+    f.render_widget(text, Rect { height: 1, ..inner });
+    if let Some(error) = input.error.as_ref().filter(|_| inner.height > 1) {
+        let error = Paragraph::new(Span::styled(error.clone(), theme.error));
+        f.render_widget(error, Rect { y: inner.y + 1, height: 1, ..inner });
+    }
+    f.set_cursor(inner.x + (input.cursor_width() - offset) as u16, inner.y);
+}
+
+// Greedy word wrap, breaking words longer than a line
+fn wrap_text(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let mut word: Vec<char> = word.chars().collect();
+        let line_len = line.chars().count();
+        if line_len > 0 && line_len + 1 + word.len() <= width {
+            line.push(' ');
+            line.extend(&word);
+            continue;
+        }
+        if line_len > 0 {
+            lines.push(std::mem::take(&mut line));
+        }
+        while word.len() > width {
+            lines.push(word.drain(..width).collect());
+        }
+        line.extend(&word);
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+// Part of the text starting at `offset` that fits in `width`, with
+// ellipses marking the hidden ends
+fn clip_text(text: &str, offset: usize, width: usize) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let offset = offset.min(chars.len().saturating_sub(1));
+    let mut visible: Vec<char> = chars[offset..].to_vec();
+    if offset > 0 && !visible.is_empty() {
+        visible[0] = '…';
+    }
+    if visible.len() > width && width > 0 {
+        visible.truncate(width - 1);
+        visible.push('…');
+    }
+    visible.into_iter().collect()
+}
+
+// Thumb drawn over the items table's right border when the items
+// don't fit. Tables don't expose their scroll offset, so the thumb
+// follows the selected row instead.
+struct Scrollbar {
+    // Lines taken by all the rows
+    lines: u32,
+    // How far down the list the selected row is, from 0 to 1
+    position: f32,
+    style: Style,
+}
+
+impl Scrollbar {
+    fn new(heights: &[u16], selected: usize, style: Style) -> Self {
+        let lines: u32 = heights.iter().map(|height| *height as u32).sum();
+        let before: u32 = heights.iter().take(selected).map(|height| *height as u32).sum();
+        let last_start = lines - heights.last().copied().unwrap_or(0) as u32;
+        Scrollbar {
+            lines,
+            position: before as f32 / last_start.max(1) as f32,
+            style,
+        }
+    }
+}
+
+impl Widget for Scrollbar {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let track = area.height as f32;
+        // Everything fits, nothing to scroll
+        if area.height == 0 || self.lines as f32 <= track {
+            return;
+        }
+        let thumb = (track * track / self.lines as f32).round().clamp(1.0, track) as u16;
+        let top = ((area.height - thumb) as f32 * self.position.min(1.0)).round() as u16;
+        for y in area.top() + top..area.top() + top + thumb {
+            buf.get_mut(area.x, y).set_symbol("█").set_style(self.style);
+        }
+    }
+}
+
+// Keys bound to an action, e.g. "j/↓"
+fn keys_to_string(keymap: &Keymap, action: Action) -> String {
+    keymap.keys(action).iter()
+        .map(|key| key.to_string())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn window_name(focused: &FocusedWindow) -> &'static str {
+    match focused {
+        FocusedWindow::Items(_) => "Items",
+        FocusedWindow::People(_) => "People",
+        FocusedWindow::Charges(_) => "Charges",
+        FocusedWindow::OwnerSelector(_,_,_) => "Select Owner",
+        FocusedWindow::RestOwnerSelector(_) => "Select Owner of Unowned Items",
+        FocusedWindow::ChargeSplitSelector(_,_,_) => "Split Charge",
+        FocusedWindow::PersonDetail(_,_) => "Breakdown",
+        FocusedWindow::AddPerson(_) => "New Person",
+        FocusedWindow::SetTotal(_,_) => "Receipt Total",
+        FocusedWindow::AddCharge(_,_) => "New Charge",
+        FocusedWindow::AddCredit(_,_) => "New Voucher",
+        FocusedWindow::SetRate(_,_) => "Exchange Rate",
+        FocusedWindow::EditItem(_,_,_) => "Edit Item",
+        FocusedWindow::AddItem(_,_) => "New Item",
+        FocusedWindow::SplitItem(_,_) => "Split Item",
+        FocusedWindow::ImportReceipt(_) => "Import Receipt",
+    }
+}
+
+// The most relevant keys for the focused window, or the last action's
+// result
+fn footer(app: &AppState) -> Paragraph<'static> {
+    if let Some(status) = &app.status {
+        return Paragraph::new(Span::styled(format!(" {}", status), app.config.theme.focus));
+    }
+    const HINTS: usize = 5;
+    let key_style = Style::default().add_modifier(Modifier::BOLD);
+    let mut spans = Vec::new();
+    let actions = if app.dialog.is_some() { &DIALOG_ACTIONS[..] } else { window_actions(&app.focused) };
+    for (action, description) in actions.iter().take(HINTS) {
+        let keys = keys_to_string(&app.config.keymap, *action);
+        if keys.is_empty() {
+            continue;
+        }
+        spans.push(Span::styled(format!(" {} ", keys), key_style));
+        spans.push(Span::raw(format!("{}  ", description)));
+    }
+    if !app.focused.is_text_input() && app.dialog.is_none() {
+        let keys = keys_to_string(&app.config.keymap, Action::Help);
+        if !keys.is_empty() {
+            spans.push(Span::styled(format!(" {} ", keys), key_style));
+            spans.push(Span::raw("Help"));
+        }
+    }
+    Paragraph::new(Spans::from(spans))
+}
+
+// Every binding for the focused window
+fn help_table(app: &AppState) -> Table<'static> {
+    let rows: Vec<Row> = window_actions(&app.focused).iter()
+        .map(|(action, description)| Row::new(vec![
+            Cell::from(Span::styled(
+                keys_to_string(&app.config.keymap, *action),
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Cell::from(*description),
+        ]))
+        .collect();
+    Table::new(rows)
+        .block(panel_block(&format!("Help: {}", window_name(&app.focused)), true, &app.config.theme))
+        .widths(&[
+            Constraint::Percentage(30),
+            Constraint::Percentage(70),
+        ])
+}
+
+// Question with its choices in a box over everything else
+fn render_dialog<B: Backend>(f: &mut Frame<B>, dialog: &Dialog, app: &AppState) {
+    const WIDTH: u16 = 50;
+    let screen = f.size();
+    let width = WIDTH.min(screen.width);
+    let mut lines: Vec<Spans> = wrap_text(&dialog.message, width.saturating_sub(4) as usize)
+        .into_iter()
+        .map(Spans::from)
+        .collect();
+    let key_style = Style::default().add_modifier(Modifier::BOLD);
+    let keymap = &app.config.keymap;
+    lines.push(Spans::default());
+    lines.push(Spans::from(vec![
+        Span::styled(format!("y/{} ", keys_to_string(keymap, Action::Select)), key_style),
+        Span::raw(format!("{}   ", dialog.confirm)),
+        Span::styled(format!("n/{} ", keys_to_string(keymap, Action::Cancel)), key_style),
+        Span::raw("Cancel"),
+    ]));
+    let height = (lines.len() as u16 + 2).min(screen.height);
+    let area = Rect {
+        x: screen.x + (screen.width - width) / 2,
+        y: screen.y + (screen.height - height) / 2,
+        width,
+        height,
+    };
+    let block = panel_block(dialog.title, true, &app.config.theme);
+    let inner = block.inner(area);
+    f.render_widget(Clear, area);
+    f.render_widget(block, area);
+    // A column of padding on either side
+    let text = Rect { x: inner.x + 1, width: inner.width.saturating_sub(2), ..inner };
+    f.render_widget(Paragraph::new(lines), text);
+}
+
+// Rect of the given percentage size centered in `area`
+fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Percentage((100 - percent_y) / 2),
+            Constraint::Percentage(percent_y),
+            Constraint::Percentage((100 - percent_y) / 2),
+        ].as_ref())
+        .split(area);
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage((100 - percent_x) / 2),
+            Constraint::Percentage(percent_x),
+            Constraint::Percentage((100 - percent_x) / 2),
+        ].as_ref())
+        .split(vertical[1])[1]
+}
+
+// Bordered panel, drawn thick and highlighted while it has focus
+fn panel_block(title: &str, focused: bool, theme: &Theme) -> Block<'static> {
+    if focused {
+        Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Thick)
+            .border_style(theme.focus)
+            .title(Span::styled(
+                title.to_string(),
+                theme.focus.add_modifier(Modifier::BOLD),
+            ))
+    }
+    else {
+        Block::default()
+            .borders(Borders::ALL)
+            .title(title.to_string())
+    }
+}
+
+// Items a person shares grouped by category, followed by their charges
+fn person_detail_table<'a>(app: &'a AppState, person_idx: usize) -> Table<'a> {
+    let breakdown = app.data.person_breakdown(person_idx);
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let format = &app.config.format;
+    let settlement = app.data.settlement_currency.as_str();
+
+    let mut rows = Vec::with_capacity(breakdown.lines());
+    for category in &breakdown.categories {
+        rows.push(Row::new(vec![
+            Cell::from(Span::styled(category.category.clone(), bold)),
+            Cell::from(""),
+            Cell::from(Span::styled(format.money_in(category.subtotal, settlement), bold)),
+        ]));
+        for (item_idx, fraction, amount) in &category.items {
+            rows.push(Row::new(vec![
+                Cell::from(format!("  {}", app.data.items[*item_idx].description)),
+                Cell::from(format!("{:.0}%", fraction * 100.0)),
+                Cell::from(format.money_in(*amount, settlement)),
+            ]));
+        }
+    }
+    if !breakdown.charges.is_empty() {
+        let charges_total = breakdown.charges.iter().fold(0.0, |total, (_, _, amount)| total + amount);
+        rows.push(Row::new(vec![
+            Cell::from(Span::styled("Charges", bold)),
+            Cell::from(""),
+            Cell::from(Span::styled(format.money_in(charges_total, settlement), bold)),
+        ]));
+        for (charge_idx, fraction, amount) in &breakdown.charges {
+            rows.push(Row::new(vec![
+                Cell::from(format!("  {}", app.data.charges[*charge_idx].description)),
+                Cell::from(format!("{:.0}%", fraction * 100.0)),
+                Cell::from(format.money_in(*amount, settlement)),
+            ]));
+        }
+    }
+    rows.push(Row::new(vec![
+        Cell::from(Span::styled("Total", bold)),
+        Cell::from(""),
+        Cell::from(Span::styled(format.money_in(breakdown.total, settlement), bold)),
+    ]));
+
+    Table::new(rows)
+        .block(panel_block(&app.data.people[person_idx], true, &app.config.theme))
+        .header(
+            Row::new(vec![
+                Cell::from(Span::styled("Description", bold)),
+                Cell::from(Span::styled("Share", bold)),
+                Cell::from(Span::styled("Amount", bold)),
+            ]).height(2),
+        )
+        .highlight_style(app.config.theme.selected)
+        .widths(&[
+            Constraint::Percentage(70),
+            Constraint::Percentage(15),
+            Constraint::Percentage(15),
+        ])
+}
@@ -0,0 +1,87 @@
+// Crash copies of sessions, written and read back from a scratch directory
+use konta::{autosave, parser, session};
+use std::fs;
+use std::path::PathBuf;
+use std::slice;
+
+// Fresh copy of sample.txt in its own directory
+fn scratch(name: &str) -> (PathBuf, PathBuf) {
+    let dir = std::env::temp_dir().join(format!("konta-test-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("sample.txt");
+    fs::copy(concat!(env!("CARGO_MANIFEST_DIR"), "/sample.txt"), &input).unwrap();
+    (dir, input)
+}
+
+#[test]
+fn unsaved_changes_are_recovered() {
+    let (dir, input) = scratch("recover");
+    let inputs = [input.clone()];
+    let loaded = parser::load(&input, "EUR").unwrap();
+    let copy = dir.join("cache").join("sample.json");
+
+    let mut changed = parser::load(&input, "EUR").unwrap();
+    changed.set_rest_items_owner(0);
+    autosave::write(&copy, &session::to_json(&changed)).unwrap();
+
+    let recovered = autosave::recover(&copy, &inputs, &loaded).expect("the copy is newer");
+    assert!(recovered.items.iter().all(|item| !item.owners.is_empty()));
+    assert_eq!(recovered.receipts[0].name, "sample");
+
+    // A copy holding nothing new isn't offered
+    autosave::write(&copy, &session::to_json(&loaded)).unwrap();
+    assert!(autosave::recover(&copy, &inputs, &loaded).is_none());
+
+    autosave::remove(&copy);
+    assert!(!copy.exists());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn copies_of_other_receipts_are_ignored() {
+    let (dir, input) = scratch("receipts");
+    let copy = dir.join("cache").join("sample.json");
+    let pharmacy = dir.join("pharmacy.txt");
+    fs::write(&pharmacy, "Farmácia\n\n    Pensos\n    1\n    0,00 €\n    4,10 €\n").unwrap();
+    let inputs = [input.clone(), pharmacy.clone()];
+    let mut both = parser::load(&input, "EUR").unwrap();
+    both.merge(parser::load(&pharmacy, "EUR").unwrap());
+
+    // A copy with a receipt imported after the files were opened is kept
+    let mut changed = parser::load(&input, "EUR").unwrap();
+    changed.merge(parser::load(&pharmacy, "EUR").unwrap());
+    changed.merge(parser::load(&pharmacy, "EUR").unwrap());
+    autosave::write(&copy, &session::to_json(&changed)).unwrap();
+    assert_eq!(autosave::recover(&copy, &inputs, &both).expect("same receipts").receipts.len(), 3);
+
+    // One missing a receipt, or with another in its place, isn't
+    let mut changed = parser::load(&input, "EUR").unwrap();
+    changed.set_rest_items_owner(0);
+    autosave::write(&copy, &session::to_json(&changed)).unwrap();
+    assert!(autosave::recover(&copy, &inputs, &both).is_none());
+    changed.merge(parser::load(&input, "EUR").unwrap());
+    autosave::write(&copy, &session::to_json(&changed)).unwrap();
+    assert!(autosave::recover(&copy, &inputs, &both).is_none());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn copies_are_per_input_file() {
+    let (dir, input) = scratch("paths");
+    let other = dir.join("other").join("sample.txt");
+    fs::create_dir_all(other.parent().unwrap()).unwrap();
+    fs::copy(&input, &other).unwrap();
+
+    let path = autosave::path(slice::from_ref(&input)).unwrap();
+    assert!(path.file_name().unwrap().to_string_lossy().starts_with("sample-"));
+    assert_ne!(path, autosave::path(slice::from_ref(&other)).unwrap());
+    assert_eq!(path, autosave::path(&[dir.join(".").join("sample.txt")]).unwrap());
+
+    // Opening it along with another receipt is another session
+    let both = autosave::path(&[input.clone(), other.clone()]).unwrap();
+    assert!(both.file_name().unwrap().to_string_lossy().starts_with("sample-"));
+    assert_ne!(path, both);
+    assert_ne!(both, autosave::path(&[other, input]).unwrap());
+    fs::remove_dir_all(dir).unwrap();
+}
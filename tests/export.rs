@@ -0,0 +1,132 @@
+// Exported splits, rendered from a session built in place
+use konta::currency::ExchangeRates;
+use konta::export::{self, ExportFormat};
+use konta::format::MoneyFormat;
+use konta::model::*;
+
+fn item(receipt: usize, description: &str, price: f32, owners: &[(usize, f32)]) -> Item {
+    Item {
+        receipt,
+        description: description.into(),
+        category: String::new(),
+        quantity: 1.0,
+        unit: String::new(),
+        price,
+        owners: owners.iter().map(|&(person, percentage)| Owner { person, percentage }).collect(),
+        flagged: false,
+        manual: false,
+    }
+}
+
+// A market paid by Ana in euros and an airport café paid by Rui in
+// dollars, at 1,25 $ to the euro
+fn trip() -> Data {
+    let mut rates = ExchangeRates::new("EUR");
+    assert!(rates.set("EUR", "USD", 1.25));
+    Data {
+        receipts: vec![
+            Receipt { name: "market".into(), currency: "EUR".into(), expected_total: None, payer: Some(0) },
+            Receipt { name: "airport".into(), currency: "USD".into(), expected_total: None, payer: Some(1) },
+        ],
+        items: vec![
+            item(0, "Queijo <&\"> | Fatiado", 10.0, &[(0, 0.5), (2, 0.5)]),
+            item(0, "Pão", 6.0, &[(1, 1.0)]),
+            item(1, "Coffee", 5.0, &[(2, 1.0)]),
+            item(1, "Sandwich", 10.0, &[(0, 1.0)]),
+        ],
+        people: vec!["Ana".into(), "Rui".into(), "Eva".into()],
+        charges: Vec::new(),
+        settlement_currency: "EUR".into(),
+        rates,
+    }
+}
+
+fn render(data: &Data, format: ExportFormat) -> String {
+    export::render(data, &MoneyFormat::default(), format)
+}
+
+#[test]
+fn text_lists_each_person_and_who_owes_whom() {
+    let text = render(&trip(), ExportFormat::Text);
+    assert_eq!(text, "\
+Split: market, airport
+
+Ana: 13,00 €
+  Queijo <&\"> | Fatiado  50%  5,00 €
+  Sandwich                    8,00 €
+
+Rui: 6,00 €
+  Pão                         6,00 €
+
+Eva: 9,00 €
+  Queijo <&\"> | Fatiado  50%  5,00 €
+  Coffee                      4,00 €
+
+Total: 28,00 €
+
+Eva owes Rui 6,00 €
+Eva owes Ana 3,00 €
+");
+}
+
+#[test]
+fn markdown_tables_escape_pipes() {
+    let markdown = render(&trip(), ExportFormat::Markdown);
+    assert!(markdown.starts_with("# Split: market, airport\n"));
+    assert!(markdown.contains("\n## Ana: 13,00 €\n"));
+    assert!(markdown.contains("| Queijo <&\"> \\| Fatiado | 50% | 5,00 € |\n"));
+    // Amounts from the dollar receipt are in euros
+    assert!(markdown.contains("| Coffee |  | 4,00 € |\n"));
+    assert!(markdown.contains("| **Total** | | **9,00 €** |\n"));
+    assert!(markdown.contains("\n**Total: 28,00 €**\n"));
+    assert!(markdown.ends_with("## Who owes whom\n\n- Eva owes Rui 6,00 €\n- Eva owes Ana 3,00 €\n"));
+}
+
+#[test]
+fn html_escapes_what_it_shows() {
+    let mut data = trip();
+    data.people[2] = "Eva <3".into();
+    data.receipts[0].name = "market & co".into();
+    let html = render(&data, ExportFormat::Html);
+    assert!(html.contains("<title>Split: market &amp; co, airport</title>"));
+    assert!(html.contains("<td>Queijo &lt;&amp;&quot;&gt; | Fatiado</td>"));
+    assert!(html.contains("<h2>Eva &lt;3: 9,00 €</h2>"));
+    assert!(html.contains("<li>Eva &lt;3 owes Rui 6,00 €</li>"));
+    assert!(!html.contains("<&\">"));
+    assert!(!html.contains("Eva <3"));
+}
+
+#[test]
+fn settlements_follow_each_receipts_payer() {
+    // Nobody paid, so nobody owes anything and the section is left out
+    let mut data = trip();
+    data.receipts.iter_mut().for_each(|receipt| receipt.payer = None);
+    assert!(!render(&data, ExportFormat::Text).contains("owes"));
+    assert!(!render(&data, ExportFormat::Markdown).contains("Who owes whom"));
+
+    // Eva paid everything, so the others owe her their share of both
+    data.receipts.iter_mut().for_each(|receipt| receipt.payer = Some(2));
+    let text = render(&data, ExportFormat::Text);
+    assert!(text.ends_with("\nAna owes Eva 13,00 €\nRui owes Eva 6,00 €\n"));
+
+    // Ana paid for all she took, and took it all
+    data.items.truncate(2);
+    data.items[0].owners.truncate(1);
+    data.items[0].owners[0].percentage = 1.0;
+    data.receipts = vec![Receipt { name: "market".into(), currency: "EUR".into(), expected_total: None, payer: Some(0) }];
+    data.items[1].owners[0].person = 0;
+    assert!(render(&data, ExportFormat::Text).ends_with("\nNobody owes anything.\n"));
+    assert!(render(&data, ExportFormat::Html).contains("<p>Nobody owes anything.</p>"));
+}
+
+#[test]
+fn unassigned_items_and_missing_rates_are_reported() {
+    let mut data = trip();
+    data.items[1].owners.clear();
+    assert!(render(&data, ExportFormat::Text).contains("\nNot assigned to anyone: 6,00 €\n"));
+    assert!(render(&data, ExportFormat::Html).contains("<p>Not assigned to anyone: 6,00 €</p>"));
+
+    data.rates = ExchangeRates::new("EUR");
+    assert_eq!(export::check_rates(&data, ExportFormat::Html).err().as_deref(), Some("no rate from USD to EUR"));
+    assert!(export::check_rates(&data, ExportFormat::Json).is_ok());
+}
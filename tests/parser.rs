@@ -0,0 +1,64 @@
+// Receipt parsing without a terminal
+use konta::parser;
+
+const RECEIPT: &str = "\
+Frigorífico
+
+    Queijo Flamengo Fatiado
+    1
+    0,00 €
+    2,35 €
+
+Frutas e Legumes
+
+    Banana Importada
+    1.83 Kg
+    -0,20 €
+    1,99 € 
+
+    Saco de Plástico
+    1
+    0,00 €
+    0,00 €
+
+Taxa de entrega: 3,99 €
+Vale de desconto: 5,00 €
+Total: 2,13 €
+";
+
+#[test]
+fn reads_items_charges_and_the_total() {
+    let data = parser::parse(RECEIPT.as_bytes(), "receipt", "EUR").unwrap();
+
+    // Free items are left out
+    assert_eq!(data.items.len(), 2);
+    let cheese = &data.items[0];
+    assert_eq!(cheese.description, "Queijo Flamengo Fatiado");
+    assert_eq!(cheese.category, "Frigorífico");
+    assert_eq!((cheese.quantity, cheese.price), (1.0, 2.35));
+    let banana = &data.items[1];
+    assert_eq!(banana.category, "Frutas e Legumes");
+    assert_eq!((banana.quantity, banana.unit.as_str(), banana.price), (1.83, "Kg", 1.99));
+
+    assert_eq!(data.charges.len(), 2);
+    assert_eq!(data.charges[0].amount, 3.99);
+    assert_eq!(data.charges[1].amount, -5.0);
+    assert_eq!(data.receipts[0].expected_total, Some(2.13));
+    assert_eq!(data.receipts[0].name, "receipt");
+}
+
+#[test]
+fn windows_line_endings_read_the_same() {
+    let unix = parser::parse(RECEIPT.as_bytes(), "receipt", "EUR").unwrap();
+    let windows = parser::parse(RECEIPT.replace('\n', "\r\n").as_bytes(), "receipt", "EUR").unwrap();
+    assert_eq!(unix.items.len(), windows.items.len());
+    for (a, b) in unix.items.iter().zip(&windows.items) {
+        assert_eq!((&a.description, &a.unit, a.price), (&b.description, &b.unit, b.price));
+    }
+    assert_eq!(windows.receipts[0].expected_total, Some(2.13));
+}
+
+#[test]
+fn missing_files_are_errors() {
+    assert!(parser::load("does-not-exist.txt", "EUR").is_err());
+}
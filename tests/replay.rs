@@ -0,0 +1,724 @@
+// Replays key presses against sample.txt without a terminal and checks
+// both the rendered screen and the resulting split
+use konta::config::Config;
+use konta::event::{Event, KeyEvent};
+use konta::input_handlers::handle_input;
+use konta::keymap::{Action, Key};
+use konta::parser;
+use konta::state::{AppState, Dialog, DialogAction, FocusedWindow, ItemField};
+use konta::ui::ui;
+use tui::backend::TestBackend;
+use tui::Terminal;
+
+// Sum of the prices in sample.txt
+const SAMPLE_TOTAL: f32 = 51.34;
+
+fn sample() -> AppState {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/sample.txt");
+    let data = parser::load(path, "EUR").expect("sample.txt parses");
+    AppState::with_config(data, Config::default())
+}
+
+// Presses space separated keys as named in the config file, e.g.
+// "a enter shift-tab", returning whether the app asked to exit
+fn press(app: &mut AppState, keys: &str) -> bool {
+    let mut exit = false;
+    for name in keys.split_whitespace() {
+        let key = Key::parse(name).unwrap_or_else(|| panic!("unknown key {}", name));
+        exit = handle_input(&Event::Input(KeyEvent::new(key.code, key.modifiers)), app);
+    }
+    exit
+}
+
+fn type_text(app: &mut AppState, text: &str) {
+    for c in text.chars() {
+        let key = Key::parse(&c.to_string()).unwrap();
+        handle_input(&Event::Input(KeyEvent::new(key.code, key.modifiers)), app);
+    }
+}
+
+fn add_person(app: &mut AppState, name: &str) {
+    press(app, "a");
+    type_text(app, name);
+    press(app, "enter");
+}
+
+fn render(app: &AppState, width: u16, height: u16) -> String {
+    let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+    terminal.draw(|f| ui(f, app)).unwrap();
+    let buffer = terminal.backend().buffer();
+    let mut screen = String::new();
+    for y in 0..buffer.area.height {
+        for x in 0..buffer.area.width {
+            screen.push_str(&buffer.get(x, y).symbol);
+        }
+        screen.push('\n');
+    }
+    screen
+}
+
+fn assert_close(actual: f32, expected: f32) {
+    assert!((actual - expected).abs() < 0.005, "expected {}, got {}", expected, actual);
+}
+
+#[test]
+fn renders_the_receipt() {
+    let app = sample();
+    let screen = render(&app, 120, 40);
+    assert!(screen.contains("Items"));
+    assert!(screen.contains("People"));
+    assert!(screen.contains("Iogurte Grego Natural"));
+    assert!(screen.contains("2,58 €"));
+    assert!(screen.contains("jojo"));
+}
+
+#[test]
+fn narrow_terminals_stack_the_panels() {
+    let app = sample();
+    let screen = render(&app, 60, 40);
+    assert!(screen.contains("Items"));
+    assert!(screen.contains("People"));
+}
+
+#[test]
+fn assigning_the_rest_gives_everything_to_one_person() {
+    let mut app = sample();
+    assert_close(app.data.unassigned_total(), SAMPLE_TOTAL);
+
+    press(&mut app, "r enter");
+    assert!(app.dialog.is_some());
+    assert_close(app.data.unassigned_total(), SAMPLE_TOTAL);
+    press(&mut app, "enter");
+    assert!(matches!(app.focused, FocusedWindow::Items(_)));
+    assert_close(app.data.compute_total()[0], SAMPLE_TOTAL);
+    assert_close(app.data.unassigned_total(), 0.0);
+    assert!(render(&app, 120, 40).contains("51,34 €"));
+}
+
+#[test]
+fn items_go_to_the_chosen_person() {
+    let mut app = sample();
+    add_person(&mut app, "Ana");
+    assert_eq!(app.data.people, ["jojo", "Ana"]);
+
+    // First item to Ana, second to jojo, the rest to Ana
+    press(&mut app, "enter down enter");
+    press(&mut app, "down enter enter");
+    press(&mut app, "r down enter y");
+
+    let totals = app.data.compute_total();
+    assert_close(totals[1], SAMPLE_TOTAL - 2.35);
+    assert_close(totals[0], 2.35);
+    assert!(render(&app, 120, 40).contains("Ana"));
+}
+
+#[test]
+fn picked_people_share_an_item() {
+    let mut app = sample();
+    add_person(&mut app, "Ana");
+
+    // Both own the yoghurt
+    press(&mut app, "enter p down enter");
+    let totals = app.data.compute_total();
+    assert_close(totals[0], 2.58 / 2.0);
+    assert_close(totals[1], 2.58 / 2.0);
+}
+
+#[test]
+fn charges_are_split_between_everyone() {
+    let mut app = sample();
+    add_person(&mut app, "Ana");
+    press(&mut app, "r enter enter");
+
+    press(&mut app, "c");
+    type_text(&mut app, "Entrega 3,00");
+    press(&mut app, "enter");
+    assert!(matches!(app.focused, FocusedWindow::ChargeSplitSelector(_,_,_)));
+    press(&mut app, "e");
+
+    let totals = app.data.compute_total();
+    assert_close(totals[0], SAMPLE_TOTAL + 1.5);
+    assert_close(totals[1], 1.5);
+    assert!(render(&app, 120, 40).contains("Entrega"));
+}
+
+#[test]
+fn a_wrong_stated_total_is_flagged() {
+    let mut app = sample();
+    press(&mut app, "t");
+    type_text(&mut app, "50,00");
+    press(&mut app, "enter");
+    assert_eq!(app.data.receipts[0].expected_total, Some(50.0));
+    assert_close(app.data.reconciliation(0).unwrap(), 50.0 - SAMPLE_TOTAL);
+}
+
+#[test]
+fn prompts_return_to_the_selected_row() {
+    let mut app = sample();
+    app.data.merge(parser::parse(PHARMACY.as_bytes(), "pharmacy", "EUR").unwrap());
+    let last = app.data.items.len() - 1;
+    app.focused = FocusedWindow::Items(last);
+
+    press(&mut app, "t");
+    type_text(&mut app, "9,99");
+    press(&mut app, "enter");
+    assert!(matches!(app.focused, FocusedWindow::Items(i) if i == last));
+    assert_eq!(app.data.receipts[1].expected_total, Some(9.99));
+    press(&mut app, "x esc c esc");
+    assert!(matches!(app.focused, FocusedWindow::Items(i) if i == last));
+    assert_eq!(app.current_receipt(), 1);
+
+    press(&mut app, "tab a");
+    type_text(&mut app, "Ana");
+    press(&mut app, "enter down a esc");
+    assert!(matches!(app.focused, FocusedWindow::People(1)));
+
+    press(&mut app, "tab c");
+    type_text(&mut app, "Entrega 2");
+    press(&mut app, "enter esc v");
+    type_text(&mut app, "Cupão 1");
+    press(&mut app, "enter esc up c esc");
+    assert!(matches!(app.focused, FocusedWindow::Charges(0)));
+}
+
+#[test]
+fn help_lists_the_bindings() {
+    let mut app = sample();
+    press(&mut app, "?");
+    let screen = render(&app, 120, 40);
+    assert!(screen.contains("Show or hide the side panels"));
+
+    // Any key closes it
+    press(&mut app, "j");
+    assert!(!render(&app, 120, 40).contains("Show or hide the side panels"));
+}
+
+#[test]
+fn quit_exits_from_panels_but_not_prompts() {
+    let mut app = sample();
+    press(&mut app, "a");
+    assert!(!press(&mut app, "q"));
+    press(&mut app, "esc");
+    assert!(press(&mut app, "q"));
+}
+
+#[test]
+fn quitting_with_unsaved_changes_asks_first() {
+    let mut app = sample();
+    press(&mut app, "enter enter");
+    assert!(!press(&mut app, "q"));
+    assert!(render(&app, 120, 40).contains("Quit without saving?"));
+
+    // Nothing is lost by answering no
+    assert!(!press(&mut app, "n"));
+    assert!(app.dialog.is_none());
+    assert!(!app.data.items[0].owners.is_empty());
+
+    // Also asked from the owner selector
+    press(&mut app, "enter");
+    assert!(!press(&mut app, "q"));
+    assert!(press(&mut app, "y"));
+}
+
+#[test]
+fn removing_people_and_items_is_confirmed() {
+    let mut app = sample();
+    let items = app.data.items.len();
+    press(&mut app, "d");
+    assert!(render(&app, 120, 40).contains("Remove Iogurte Grego Natural"));
+    press(&mut app, "esc");
+    assert_eq!(app.data.items.len(), items);
+
+    add_person(&mut app, "Ana");
+    press(&mut app, "tab down d");
+    assert!(render(&app, 120, 40).contains("Remove Ana?"));
+    // Other keys leave the question open
+    press(&mut app, "j");
+    assert_eq!(app.data.people.len(), 2);
+    press(&mut app, "y");
+    assert_eq!(app.data.people, ["jojo"]);
+    assert!(matches!(app.focused, FocusedWindow::People(0)));
+}
+
+#[test]
+fn removing_a_charge_is_confirmed() {
+    let mut app = sample();
+    for charge in ["Entrega 3", "Saco 0,10", "Gorjeta 1"] {
+        press(&mut app, "c");
+        type_text(&mut app, charge);
+        press(&mut app, "enter e");
+    }
+    press(&mut app, "up d");
+    assert!(render(&app, 120, 40).contains("Remove Saco?"));
+    press(&mut app, "n");
+    assert_eq!(app.data.charges.len(), 3);
+
+    // The charge after it takes its place, or the one before at the end
+    press(&mut app, "d y");
+    let left: Vec<_> = app.data.charges.iter().map(|charge| charge.description.as_str()).collect();
+    assert_eq!(left, ["Entrega", "Gorjeta"]);
+    assert!(matches!(app.focused, FocusedWindow::Charges(1)));
+    press(&mut app, "d y");
+    assert!(matches!(app.focused, FocusedWindow::Charges(0)));
+}
+
+#[test]
+fn sessions_are_saved_from_the_charges_panel() {
+    let mut app = sample();
+    let path = std::env::temp_dir().join(format!("charges-{}.konta.json", std::process::id()));
+    app.session_path = Some(path.clone());
+    press(&mut app, "c");
+    type_text(&mut app, "Entrega 3,00");
+    press(&mut app, "enter e");
+    assert!(matches!(app.focused, FocusedWindow::Charges(_)));
+
+    press(&mut app, "ctrl-s");
+    assert_eq!(app.status, Some(format!("Saved to {}", path.display())));
+    assert!(!app.is_dirty());
+    let saved = konta::session::load(&path, "EUR").unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(saved.charges[0].description, "Entrega");
+}
+
+#[test]
+fn saving_over_an_earlier_session_is_confirmed() {
+    let mut app = sample();
+    let path = std::env::temp_dir().join(format!("earlier-{}.konta.json", std::process::id()));
+    std::fs::write(&path, "earlier").unwrap();
+    app.session_path = Some(path.clone());
+
+    press(&mut app, "ctrl-s");
+    assert!(render(&app, 120, 40).contains("already exists"));
+    press(&mut app, "n");
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "earlier");
+
+    press(&mut app, "ctrl-s y");
+    assert!(konta::session::load(&path, "EUR").is_ok());
+    // Once saved it's this session's file
+    press(&mut app, "enter enter ctrl-s");
+    assert!(app.dialog.is_none());
+    assert!(!app.is_dirty());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn weights_keep_their_unit() {
+    let app = sample();
+    let banana = &app.data.items[5];
+    assert!(banana.description.starts_with("Banana"));
+    assert_close(banana.quantity, 1.83);
+    assert_eq!(banana.unit, "Kg");
+    assert!(render(&app, 120, 40).contains("1,83 Kg"));
+}
+
+#[test]
+fn edited_prices_update_the_totals() {
+    let mut app = sample();
+    press(&mut app, "r enter enter");
+
+    // Tab through description, quantity and unit to the price
+    press(&mut app, "i tab tab tab");
+    assert!(matches!(app.focused, FocusedWindow::EditItem(0, ItemField::Price, _)));
+    press(&mut app, &"backspace ".repeat(10));
+    type_text(&mut app, "3,00");
+    press(&mut app, "enter");
+
+    assert_close(app.data.items[0].price, 3.0);
+    assert_close(app.data.compute_total()[0], SAMPLE_TOTAL - 2.58 + 3.0);
+    assert!(matches!(app.focused, FocusedWindow::Items(0)));
+}
+
+#[test]
+fn invalid_fields_are_not_saved() {
+    let mut app = sample();
+    press(&mut app, "i tab backspace");
+    type_text(&mut app, "0");
+    press(&mut app, "enter");
+    assert!(matches!(app.focused, FocusedWindow::EditItem(0, ItemField::Quantity, _)));
+    assert!(app.focused.text_input().unwrap().error.is_some());
+    assert_close(app.data.items[0].quantity, 2.0);
+
+    // Leaving discards the field being edited
+    press(&mut app, "esc");
+    assert!(matches!(app.focused, FocusedWindow::Items(0)));
+    assert_close(app.data.items[0].quantity, 2.0);
+}
+
+#[test]
+fn added_items_count_towards_the_totals() {
+    let mut app = sample();
+    let items = app.data.items.len();
+    press(&mut app, "n");
+    type_text(&mut app, "Pão de Deus 1,20");
+    press(&mut app, "enter");
+
+    assert_eq!(app.data.items.len(), items + 1);
+    assert_eq!(app.data.items[items].description, "Pão de Deus");
+    assert!(matches!(app.focused, FocusedWindow::Items(i) if i == items));
+    assert_close(app.data.items_total(0), SAMPLE_TOTAL + 1.2);
+}
+
+#[test]
+fn split_lines_keep_the_price_and_owners() {
+    let mut app = sample();
+    add_person(&mut app, "Ana");
+    press(&mut app, "r enter enter");
+
+    // One of the two yoghurt packs goes to Ana
+    press(&mut app, "/");
+    type_text(&mut app, "1");
+    press(&mut app, "enter");
+    assert!(matches!(app.focused, FocusedWindow::Items(1)));
+    assert_close(app.data.items[0].quantity + app.data.items[1].quantity, 2.0);
+    assert_close(app.data.items[0].price + app.data.items[1].price, 2.58);
+    assert_close(app.data.compute_total()[0], SAMPLE_TOTAL);
+
+    press(&mut app, "enter down enter");
+    let totals = app.data.compute_total();
+    assert_close(totals[1], 1.29);
+    assert_close(totals[0], SAMPLE_TOTAL - 1.29);
+}
+
+#[test]
+fn splitting_the_whole_quantity_is_refused() {
+    let mut app = sample();
+    let items = app.data.items.len();
+    press(&mut app, "/");
+    type_text(&mut app, "2");
+    press(&mut app, "enter");
+    assert!(matches!(app.focused, FocusedWindow::SplitItem(0, _)));
+    assert_eq!(app.data.items.len(), items);
+}
+
+#[test]
+fn duplicated_and_removed_items() {
+    let mut app = sample();
+    let items = app.data.items.len();
+    press(&mut app, "enter enter y");
+    assert_eq!(app.data.items.len(), items + 1);
+    assert!(matches!(app.focused, FocusedWindow::Items(1)));
+    assert_eq!(app.data.items[1].description, app.data.items[0].description);
+    assert_close(app.data.compute_total()[0], 2.0 * 2.58);
+
+    press(&mut app, "d enter d enter");
+    assert_eq!(app.data.items.len(), items - 1);
+    assert_close(app.data.compute_total()[0], 2.58);
+}
+
+fn prompt_text(app: &AppState) -> &str {
+    app.focused.text_input().expect("a prompt is open").text()
+}
+
+#[test]
+fn prompts_edit_at_the_cursor() {
+    let mut app = sample();
+    press(&mut app, "a");
+    type_text(&mut app, "Joo Silva");
+    press(&mut app, "home right right");
+    type_text(&mut app, "ã");
+    assert_eq!(prompt_text(&app), "João Silva");
+
+    // Whole graphemes go at once, even when combining
+    press(&mut app, "end ctrl-w");
+    type_text(&mut app, "Ze\u{301}");
+    press(&mut app, "backspace");
+    assert_eq!(prompt_text(&app), "João Z");
+
+    press(&mut app, "ctrl-u");
+    assert_eq!(prompt_text(&app), "");
+}
+
+#[test]
+fn pasted_text_is_inserted_at_once() {
+    let mut app = sample();
+    press(&mut app, "a");
+    handle_input(&Event::Paste("Ana\nMaria".into()), &mut app);
+    assert_eq!(prompt_text(&app), "Ana Maria");
+
+    // Outside prompts pasting types the keys
+    press(&mut app, "enter");
+    handle_input(&Event::Paste("jj".into()), &mut app);
+    assert!(matches!(app.focused, FocusedWindow::Items(2)));
+}
+
+#[test]
+fn names_must_be_new_and_not_empty() {
+    let mut app = sample();
+    press(&mut app, "a");
+    type_text(&mut app, "  ");
+    press(&mut app, "enter");
+    assert!(app.focused.text_input().unwrap().error.is_some());
+
+    press(&mut app, "ctrl-u");
+    type_text(&mut app, "JOJO");
+    press(&mut app, "enter");
+    assert!(app.focused.text_input().unwrap().error.is_some());
+    assert!(render(&app, 120, 40).contains("JOJO is already on the list"));
+    assert_eq!(app.data.people.len(), 1);
+
+    // Editing clears the message
+    press(&mut app, "backspace");
+    assert!(app.focused.text_input().unwrap().error.is_none());
+}
+
+#[test]
+fn reloading_keeps_owners_and_flags_changes() {
+    let mut app = sample();
+    add_person(&mut app, "Ana");
+    // Yoghurt to Ana, cheese to jojo
+    press(&mut app, "enter down enter down enter enter");
+    let items = app.data.items.len();
+
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/sample.txt");
+    let text = std::fs::read_to_string(path).unwrap()
+        .replacen("2,35 €", "2,99 €", 1)
+        .replace("Creme de Cenoura", "Sopa de Cenoura");
+    let fresh = parser::parse(text.as_bytes(), "sample", "EUR").unwrap();
+    app.reload(0, fresh);
+
+    assert_eq!(app.data.items.len(), items);
+    let yoghurt = &app.data.items[0];
+    assert!(!yoghurt.flagged);
+    assert_eq!(yoghurt.owners[0].person, 1);
+    // A new price keeps the owner but asks for another look
+    let cheese = &app.data.items[1];
+    assert!(cheese.flagged);
+    assert_close(cheese.price, 2.99);
+    assert_eq!(cheese.owners[0].person, 0);
+    let soup = app.data.items.iter().find(|item| item.description == "Sopa de Cenoura").unwrap();
+    assert!(soup.flagged && soup.owners.is_empty());
+    assert!(render(&app, 120, 40).contains("● Sopa de Cenoura"));
+
+    // Assigning clears the flag
+    press(&mut app, "enter enter");
+    assert!(!app.data.items[1].flagged);
+}
+
+#[test]
+fn reloading_keeps_what_was_added_by_hand() {
+    let mut app = sample();
+    press(&mut app, "n");
+    type_text(&mut app, "Pensos 1,00");
+    press(&mut app, "enter t");
+    type_text(&mut app, "60");
+    press(&mut app, "enter c");
+    type_text(&mut app, "Entrega 3,99");
+    press(&mut app, "enter esc");
+    // Half the yoghurt pack on its own line, and the cheese twice
+    app.focused = FocusedWindow::Items(0);
+    press(&mut app, "/ 1 enter");
+    app.focused = FocusedWindow::Items(2);
+    press(&mut app, "y");
+    let items = app.data.items.len();
+    assert_eq!(app.data.charges.len(), 1);
+
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/sample.txt");
+    app.reload(0, parser::load(path, "EUR").unwrap());
+    assert_eq!(app.data.items.len(), items);
+    assert!(app.data.items.iter().all(|item| !item.flagged));
+    assert!(app.data.items.iter().any(|item| item.description == "Pensos"));
+    assert_close(app.data.items[0].quantity + app.data.items[1].quantity, 2.0);
+    assert_eq!(app.data.charges.len(), 1);
+    assert_eq!(app.data.receipts[0].expected_total, Some(60.0));
+    assert_eq!(app.status.as_deref(), Some("Reloaded sample: 0 items new or changed, 0 removed"));
+
+    // A total stated in the file wins
+    let text = std::fs::read_to_string(path).unwrap() + "\nTotal: 51,34 €\n";
+    app.reload(0, parser::parse(text.as_bytes(), "sample", "EUR").unwrap());
+    assert_eq!(app.data.receipts[0].expected_total, Some(51.34));
+    assert_eq!(app.data.items.len(), items);
+}
+
+const PHARMACY: &str = "\
+Farmácia
+
+    Paracetamol 1 g
+    1
+    0,00 €
+    3,20 €
+
+    Pensos
+    2
+    0,00 €
+    4,10 €
+";
+
+#[test]
+fn imported_receipts_are_split_together() {
+    let mut app = sample();
+    add_person(&mut app, "Ana");
+    let path = std::env::temp_dir().join(format!("pharmacy-{}.txt", std::process::id()));
+    std::fs::write(&path, PHARMACY).unwrap();
+
+    press(&mut app, "f");
+    handle_input(&Event::Paste(path.display().to_string()), &mut app);
+    press(&mut app, "enter");
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(app.data.receipts.len(), 2);
+    assert_eq!(app.data.people, ["jojo", "Ana"]);
+    assert!(matches!(app.focused, FocusedWindow::Items(i) if app.data.items[i].description == "Paracetamol 1 g"));
+    assert_close(app.data.receipt_total(0), SAMPLE_TOTAL);
+    assert_close(app.data.receipt_total(1), 7.3);
+    let screen = render(&app, 120, 40);
+    assert!(screen.contains("Receipt "));
+    assert!(screen.contains("7,30 €"));
+
+    // Items added to a receipt stay with it
+    press(&mut app, "n");
+    type_text(&mut app, "Pensos 1,00");
+    press(&mut app, "enter");
+    let last = app.data.items.len() - 1;
+    assert!(matches!(app.focused, FocusedWindow::Items(i) if i == last));
+    assert_eq!(app.data.items[last].receipt, 1);
+
+    press(&mut app, "r enter enter");
+    assert_close(app.data.compute_total()[0], SAMPLE_TOTAL + 8.3);
+}
+
+#[test]
+fn proportional_charges_follow_their_receipt() {
+    let mut app = sample();
+    add_person(&mut app, "Ana");
+    press(&mut app, "r enter enter");
+    let pharmacy = parser::parse(PHARMACY.as_bytes(), "pharmacy", "EUR").unwrap();
+    app.data.merge(pharmacy);
+    press(&mut app, "r down enter enter");
+
+    // Ana owns everything on the pharmacy receipt, so its delivery is
+    // all hers however much jojo bought elsewhere
+    app.data.add_charge(konta::model::Charge {
+        receipt: 1,
+        description: "Delivery".into(),
+        amount: 2.0,
+        split: konta::model::SplitPolicy::Proportional,
+        manual: true,
+    });
+    let totals = app.data.compute_total();
+    assert_close(totals[0], SAMPLE_TOTAL);
+    assert_close(totals[1], 9.3);
+    assert_close(app.data.receipt_shares(1)[1], 9.3);
+    assert_close(app.data.person_breakdown(1).total, 9.3);
+}
+
+#[test]
+fn imports_bring_their_currency_and_rates() {
+    let mut app = sample();
+    app.receipt_currency = "USD".into();
+    let path = std::env::temp_dir().join(format!("pharmacy-usd-{}.txt", std::process::id()));
+    std::fs::write(&path, PHARMACY).unwrap();
+    app.import(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(app.data.receipts[1].currency, "USD");
+    assert!(app.data.missing_rates() == ["USD"]);
+
+    // A session that knows the rate fills it in
+    let mut session = parser::parse(PHARMACY.as_bytes(), "pharmacy", "USD").unwrap();
+    session.settlement_currency = "EUR".into();
+    assert!(session.rates.set("USD", "EUR", 0.5));
+    let path = std::env::temp_dir().join(format!("pharmacy-{}.konta.json", std::process::id()));
+    std::fs::write(&path, konta::session::to_json(&session)).unwrap();
+    app.import(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(app.data.missing_rates().is_empty());
+    assert_close(app.data.to_settlement(7.3, 1), 3.65);
+}
+
+#[test]
+fn payers_are_set_for_the_last_selected_receipt() {
+    let mut app = sample();
+    add_person(&mut app, "Ana");
+    app.data.merge(parser::parse(PHARMACY.as_bytes(), "pharmacy", "EUR").unwrap());
+    let last = app.data.items.len() - 1;
+    app.focused = FocusedWindow::Items(last);
+
+    press(&mut app, "tab down $");
+    assert_eq!(app.data.receipts[1].payer, Some(1));
+    assert_eq!(app.data.receipts[0].payer, None);
+    assert_eq!(app.status.as_deref(), Some("Ana paid pharmacy"));
+    assert!(render(&app, 120, 40).contains("Ana $ pharmacy"));
+
+    // Selecting the first receipt again moves what the people panel
+    // acts on
+    app.focused = FocusedWindow::Items(0);
+    press(&mut app, "tab $");
+    assert_eq!(app.data.receipts[0].payer, Some(0));
+    assert!(render(&app, 120, 40).contains("jojo $ sample"));
+}
+
+#[test]
+fn recovered_sessions_keep_watching_their_receipts() {
+    let mut app = sample();
+    let sample_path = std::path::PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/sample.txt"));
+    app.receipt_files = vec![Some(sample_path)];
+
+    // The crash copy had a receipt imported into it
+    let mut recovered = parser::load(concat!(env!("CARGO_MANIFEST_DIR"), "/sample.txt"), "EUR").unwrap();
+    recovered.merge(parser::parse(PHARMACY.as_bytes(), "pharmacy", "EUR").unwrap());
+    app.dialog = Some(Dialog {
+        title: "Recover",
+        message: "Recover them?".into(),
+        confirm: "Recover",
+        action: DialogAction::Recover(Box::new(recovered)),
+    });
+    press(&mut app, "y");
+    assert_eq!(app.data.receipts.len(), 2);
+    assert_eq!(app.receipt_files.len(), 2);
+    assert!(app.receipt_files[1].is_none());
+
+    // Reloads of receipts that are no longer there are dropped
+    let items = app.data.items.len();
+    app.reload(2, parser::parse(PHARMACY.as_bytes(), "pharmacy", "EUR").unwrap());
+    assert_eq!(app.data.items.len(), items);
+}
+
+#[test]
+fn missing_imports_are_reported() {
+    let mut app = sample();
+    press(&mut app, "f");
+    type_text(&mut app, "nowhere.txt");
+    press(&mut app, "enter");
+    assert!(matches!(app.focused, FocusedWindow::ImportReceipt(_)));
+    assert!(app.focused.text_input().unwrap().error.is_some());
+    assert_eq!(app.data.receipts.len(), 1);
+}
+
+#[test]
+fn small_rates_keep_their_decimals() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/sample.txt");
+    let mut data = parser::load(path, "JPY").unwrap();
+    data.settlement_currency = "EUR".into();
+    data.rates = konta::currency::ExchangeRates::new("EUR");
+    let mut app = AppState::with_config(data, Config::default());
+
+    press(&mut app, "x");
+    type_text(&mut app, "0,0061");
+    press(&mut app, "enter");
+    assert!(render(&app, 120, 40).contains("1 JPY = 0,0061 EUR"));
+    press(&mut app, "x");
+    assert_eq!(prompt_text(&app), "0,0061");
+}
+
+#[test]
+fn rates_the_table_cannot_hold_are_refused() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/sample.txt");
+    let mut data = parser::load(path, "USD").unwrap();
+    data.settlement_currency = "EUR".into();
+    // Neither USD nor EUR is the base or has a rate
+    data.rates = konta::currency::ExchangeRates::new("GBP");
+    let mut config = Config::default();
+    config.keymap.bind(Key::parse("R").unwrap(), Action::SetRate);
+    let mut app = AppState::with_config(data, config);
+    assert!(render(&app, 120, 40).contains("No USD rate: press R/x"));
+
+    press(&mut app, "x");
+    type_text(&mut app, "0,9");
+    press(&mut app, "enter");
+    assert!(matches!(app.focused, FocusedWindow::SetRate(0, _)));
+    assert!(app.focused.text_input().unwrap().error.is_some());
+    assert!(app.data.rates.convert(1.0, "USD", "EUR").is_none());
+
+    // Nor is the split exported without it
+    press(&mut app, "esc o");
+    assert_eq!(app.status.as_deref(), Some("Export failed: no rate from USD to EUR"));
+}
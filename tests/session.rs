@@ -0,0 +1,130 @@
+// Sessions written as JSON and read back, and the files that are refused
+use konta::currency::ExchangeRates;
+use konta::model::*;
+use konta::session::{self, VERSION};
+
+fn receipt(name: &str, currency: &str, expected_total: Option<f32>, payer: Option<usize>) -> Receipt {
+    Receipt { name: name.into(), currency: currency.into(), expected_total, payer }
+}
+
+fn item(receipt: usize, description: &str, price: f32, owners: Vec<Owner>) -> Item {
+    Item {
+        receipt,
+        description: description.into(),
+        category: "Mercearia".into(),
+        quantity: 1.5,
+        unit: "Kg".into(),
+        price,
+        owners,
+        flagged: false,
+        manual: receipt == 1,
+    }
+}
+
+// Two receipts in different currencies, with shared items, a payer for
+// each and every kind of charge split
+fn session() -> Data {
+    let mut rates = ExchangeRates::new("EUR");
+    assert!(rates.set("USD", "EUR", 0.9));
+    Data {
+        receipts: vec![
+            receipt("market", "EUR", Some(12.5), Some(1)),
+            receipt("airport", "USD", None, Some(2)),
+        ],
+        items: vec![
+            item(0, "Queijo", 4.0, vec![Owner { person: 0, percentage: 1.0 }]),
+            item(0, "Pão", 8.5, vec![
+                Owner { person: 1, percentage: 0.25 },
+                Owner { person: 2, percentage: 0.75 },
+            ]),
+            item(1, "Coffee", 6.0, vec![]),
+        ],
+        people: vec!["Ana".into(), "Rui".into(), "Eva".into()],
+        charges: vec![
+            Charge { receipt: 0, description: "Delivery".into(), amount: 3.0, split: SplitPolicy::Equal, manual: false },
+            Charge { receipt: 0, description: "Voucher".into(), amount: -2.0, split: SplitPolicy::Proportional, manual: false },
+            Charge { receipt: 1, description: "Tip".into(), amount: 1.0, split: SplitPolicy::People(vec![2, 0]), manual: true },
+        ],
+        settlement_currency: "EUR".into(),
+        rates,
+    }
+}
+
+fn owners(item: &Item) -> Vec<(usize, f32)> {
+    item.owners.iter().map(|owner| (owner.person, owner.percentage)).collect()
+}
+
+#[test]
+fn sessions_read_back_the_same() {
+    let data = session();
+    let read = session::from_json(&session::to_json(&data), "unused", "GBP").unwrap();
+
+    assert_eq!(read.people, data.people);
+    assert_eq!(read.settlement_currency, "EUR");
+    assert_eq!(read.receipts.len(), 2);
+    for (a, b) in read.receipts.iter().zip(&data.receipts) {
+        assert_eq!((&a.name, &a.currency, a.expected_total, a.payer), (&b.name, &b.currency, b.expected_total, b.payer));
+    }
+
+    assert_eq!(read.items.len(), data.items.len());
+    for (a, b) in read.items.iter().zip(&data.items) {
+        assert_eq!((a.receipt, &a.description, &a.category), (b.receipt, &b.description, &b.category));
+        assert_eq!((a.quantity, &a.unit, a.price, a.manual), (b.quantity, &b.unit, b.price, b.manual));
+        assert_eq!(owners(a), owners(b));
+    }
+
+    assert_eq!(read.charges.len(), data.charges.len());
+    for (a, b) in read.charges.iter().zip(&data.charges) {
+        assert_eq!((a.receipt, &a.description, a.amount, a.manual), (b.receipt, &b.description, b.amount, b.manual));
+        match (&a.split, &b.split) {
+            (SplitPolicy::Equal, SplitPolicy::Equal) | (SplitPolicy::Proportional, SplitPolicy::Proportional) => {}
+            (SplitPolicy::People(a), SplitPolicy::People(b)) => assert_eq!(a, b),
+            _ => panic!("'{}' is split differently", a.description),
+        }
+    }
+
+    assert_eq!(read.rates.convert(10.0, "USD", "EUR"), Some(9.0));
+    assert_eq!(session::to_json(&read), session::to_json(&data));
+}
+
+#[test]
+fn sessions_without_receipts_get_one() {
+    let read = session::from_json(r#"{"version": 1, "people": [], "items": [{"description": "Pão", "price": 1}]}"#, "market", "GBP").unwrap();
+    assert_eq!(read.receipts.len(), 1);
+    assert_eq!((read.receipts[0].name.as_str(), read.receipts[0].currency.as_str()), ("market", "GBP"));
+    assert_eq!(read.settlement_currency, "GBP");
+    assert_eq!(read.items[0].quantity, 1.0);
+}
+
+// Saves `session()` with `change` made to its JSON and reads it back
+fn read_changed(change: impl Fn(&mut serde_json::Value)) -> Result<Data, String> {
+    let mut json: serde_json::Value = serde_json::from_str(&session::to_json(&session())).unwrap();
+    change(&mut json);
+    session::from_json(&json.to_string(), "unused", "EUR")
+}
+
+#[test]
+fn invalid_sessions_are_refused() {
+    let error = read_changed(|json| json["items"][0]["owners"][0]["person"] = "Zé".into());
+    assert_eq!(error.err().as_deref(), Some("unknown person 'Zé'"));
+
+    let error = read_changed(|json| json["charges"][2]["split"]["people"][1] = "Zé".into());
+    assert_eq!(error.err().as_deref(), Some("unknown person 'Zé'"));
+
+    let error = read_changed(|json| json["receipts"][1]["payer"] = "Zé".into());
+    assert_eq!(error.err().as_deref(), Some("unknown person 'Zé'"));
+
+    let error = read_changed(|json| json["people"][2]["name"] = "Ana".into());
+    assert_eq!(error.err().as_deref(), Some("person 'Ana' appears twice"));
+
+    for share in [0.0, -0.5, 1.5] {
+        let error = read_changed(|json| json["items"][1]["owners"][0]["share"] = share.into());
+        assert_eq!(error.err(), Some(format!("invalid share {} of 'Pão'", share as f32)));
+    }
+
+    let error = read_changed(|json| json["version"] = (VERSION + 1).into());
+    assert_eq!(error.err(), Some(format!("unsupported session version {}", VERSION + 1)));
+
+    let error = read_changed(|json| json["items"][2]["receipt"] = 2.into());
+    assert_eq!(error.err().as_deref(), Some("unknown receipt 2"));
+}
@@ -0,0 +1,21 @@
+// Colours as written in the config file
+use konta::config::Config;
+use konta::theme::parse_color;
+use tui::style::Color;
+
+#[test]
+fn colours_are_read_by_name_hex_and_index() {
+    assert_eq!(parse_color("light-red"), Some(Color::LightRed));
+    assert_eq!(parse_color("Dark Grey"), Some(Color::DarkGray));
+    assert_eq!(parse_color("#FF8800"), Some(Color::Rgb(0xff, 0x88, 0x00)));
+    assert_eq!(parse_color("214"), Some(Color::Indexed(214)));
+}
+
+#[test]
+fn bad_colours_are_refused() {
+    for text in ["", "#ff88", "#ff88zz", "256", "purple", "#a\u{e9}\u{e9}b", "#\u{e9}\u{e9}\u{e9}"] {
+        assert_eq!(parse_color(text), None, "{:?}", text);
+    }
+    let error = Config::parse("[theme]\nfocus = \"#a\u{e9}\u{e9}b\"\n").err();
+    assert_eq!(error.as_deref(), Some("invalid colour '#a\u{e9}\u{e9}b'"));
+}